@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+/// Latencies and error counts collected by `call --bench`.
+#[derive(Debug, Clone, Default)]
+pub struct BenchStats {
+    latencies: Vec<Duration>,
+    errors: u64,
+    elapsed: Duration,
+}
+
+impl BenchStats {
+    /// Records a successful call.
+    pub fn record(&mut self, latency: Duration) {
+        self.latencies.push(latency);
+    }
+
+    /// Records a call that failed or timed out.
+    pub fn record_error(&mut self) {
+        self.errors += 1;
+    }
+
+    /// Sets the wall-clock time the calls took, used for the throughput.
+    pub fn set_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed = elapsed;
+    }
+
+    /// Adds the calls of another session. The elapsed time is left untouched
+    /// because sessions overlap; the caller sets the total wall-clock time.
+    pub fn merge(&mut self, other: &BenchStats) {
+        self.latencies.extend_from_slice(&other.latencies);
+        self.errors += other.errors;
+    }
+
+    /// Formats a summary such as:
+    ///
+    /// ```text
+    /// Total: 1000 calls, 2 errors in 1.25s (800.0 calls/s)
+    ///   latency min 0.41ms  mean 1.20ms  p50 1.02ms  p90 1.87ms  p99 4.12ms  max 9.30ms
+    /// ```
+    pub fn report(&self, label: &str) -> String {
+        let calls = self.latencies.len() as u64 + self.errors;
+        let seconds = self.elapsed.as_secs_f64();
+        let rate = if seconds > 0.0 {
+            calls as f64 / seconds
+        } else {
+            0.0
+        };
+
+        let mut report = format!(
+            "{}{} calls, {} errors in {:.2}s ({:.1} calls/s)",
+            label, calls, self.errors, seconds, rate
+        );
+
+        if self.latencies.is_empty() {
+            return report;
+        }
+
+        let mut sorted = self.latencies.clone();
+        sorted.sort_unstable();
+        let total: Duration = sorted.iter().sum();
+        let mean = total / sorted.len() as u32;
+
+        report.push_str(&format!(
+            "\n  latency min {}  mean {}  p50 {}  p90 {}  p99 {}  max {}",
+            format_ms(sorted[0]),
+            format_ms(mean),
+            format_ms(percentile(&sorted, 50.0)),
+            format_ms(percentile(&sorted, 90.0)),
+            format_ms(percentile(&sorted, 99.0)),
+            format_ms(sorted[sorted.len() - 1]),
+        ));
+        report
+    }
+}
+
+/// Nearest-rank percentile of an already sorted slice, or zero if it is empty.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn format_ms(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(values: &[u64]) -> Vec<Duration> {
+        values.iter().map(|&v| Duration::from_millis(v)).collect()
+    }
+
+    #[test]
+    fn percentile_of_empty_is_zero() {
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+        assert_eq!(percentile(&[], 99.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn percentile_of_one_sample() {
+        let sorted = ms(&[7]);
+        assert_eq!(percentile(&sorted, 0.0), Duration::from_millis(7));
+        assert_eq!(percentile(&sorted, 50.0), Duration::from_millis(7));
+        assert_eq!(percentile(&sorted, 100.0), Duration::from_millis(7));
+    }
+
+    #[test]
+    fn percentile_of_two_samples() {
+        let sorted = ms(&[1, 9]);
+        assert_eq!(percentile(&sorted, 50.0), Duration::from_millis(1));
+        assert_eq!(percentile(&sorted, 51.0), Duration::from_millis(9));
+        assert_eq!(percentile(&sorted, 99.0), Duration::from_millis(9));
+    }
+
+    #[test]
+    fn p99_of_few_samples_is_the_maximum() {
+        let sorted = ms(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(percentile(&sorted, 90.0), Duration::from_millis(9));
+        assert_eq!(percentile(&sorted, 99.0), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn report_counts_errors_as_calls() {
+        let mut stats = BenchStats::default();
+        stats.record(Duration::from_millis(2));
+        stats.record_error();
+        stats.set_elapsed(Duration::from_secs(1));
+        assert!(
+            stats
+                .report("")
+                .starts_with("2 calls, 1 errors in 1.00s (2.0 calls/s)")
+        );
+    }
+}
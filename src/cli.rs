@@ -1,40 +1,77 @@
+use crate::filter::Filter;
+use crate::output::OutputFormat;
+use crate::utils::{BytesFormat, parse_duration, parse_rate, parse_timeout};
 use clap::{Parser, Subcommand};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "wick")]
 #[command(about = "WAMP Command Line Interface", long_about = None)]
 pub struct Cli {
-    /// The URL of the router to connect to
-    #[arg(long, default_value = "ws://localhost:8080/ws", global = true)]
-    pub url: String,
+    /// The URL of the router to connect to (ws://, wss://, rs://, rss://, tcp://, tcps://, unix://) [default: ws://localhost:8080/ws]
+    #[arg(long, env = "WICK_URL", global = true)]
+    pub url: Option<String>,
 
-    /// The realm to join
-    #[arg(long, default_value = "realm1", global = true)]
-    pub realm: String,
+    /// The realm to join [default: realm1]
+    #[arg(long, env = "WICK_REALM", global = true)]
+    pub realm: Option<String>,
 
     /// The authid to use, if authenticating.
-    #[arg(long, global = true)]
+    #[arg(long, env = "WICK_AUTHID", global = true)]
     pub authid: Option<String>,
 
     /// The authrole to use, if authenticating.
-    #[arg(long, global = true)]
+    #[arg(long, env = "WICK_AUTHROLE", global = true)]
     pub authrole: Option<String>,
 
-    /// The secret to use in Challenge-Response Auth.
+    /// The secret to use in Challenge-Response Auth. Falls back to $WICK_SECRET.
     #[arg(long, global = true)]
     pub secret: Option<String>,
 
-    /// The ed25519 private key hex for cryptosign.
+    /// Read the Challenge-Response Auth secret from a file. Falls back to $WICK_SECRET_FILE.
+    #[arg(long, value_name = "FILE", global = true)]
+    pub secret_file: Option<String>,
+
+    /// Prompt for the Challenge-Response Auth secret on the terminal without echoing it.
+    #[arg(long, conflicts_with_all = ["secret", "secret_file"], global = true)]
+    pub ask_secret: bool,
+
+    /// The ed25519 private key hex for cryptosign. Falls back to $WICK_PRIVATE_KEY.
     #[arg(long, global = true)]
     pub private_key: Option<String>,
 
-    /// The ticket when using ticket authentication.
+    /// Read the ed25519 private key hex from a file, e.g. the 'key' file written by `wick keygen -O`.
+    /// Falls back to $WICK_PRIVATE_KEY_FILE.
+    #[arg(long, value_name = "FILE", global = true)]
+    pub private_key_file: Option<String>,
+
+    /// The ticket when using ticket authentication. Falls back to $WICK_TICKET.
     #[arg(long, global = true)]
     pub ticket: Option<String>,
 
-    /// Serializer to use (json, msgpack, cbor)
-    #[arg(long, default_value = "json", global = true)]
-    pub serializer: String,
+    /// Read the ticket from a file. Falls back to $WICK_TICKET_FILE.
+    #[arg(long, value_name = "FILE", global = true)]
+    pub ticket_file: Option<String>,
+
+    /// Serializer to use (json, msgpack, cbor) [default: json]
+    #[arg(long, env = "WICK_SERIALIZER", global = true)]
+    pub serializer: Option<String>,
+
+    /// Output format for results, events and invocations
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pretty, env = "WICK_OUTPUT", global = true)]
+    pub output: OutputFormat,
+
+    /// How binary values are printed in results, events and invocations
+    #[arg(long, value_enum, default_value_t = BytesFormat::Hex, env = "WICK_BYTES_FORMAT", global = true)]
+    pub bytes_format: BytesFormat,
+
+    /// Path to the config file with connection profiles [default: ~/.config/wick/config.toml]
+    #[arg(long, value_name = "FILE", env = "WICK_CONFIG", global = true)]
+    pub config: Option<String>,
+
+    /// Connection profile to use from the config file. Command line options take precedence.
+    #[arg(long, value_name = "NAME", env = "WICK_PROFILE", global = true)]
+    pub profile: Option<String>,
 
     #[command(subcommand)]
     pub command: Commands,
@@ -49,9 +86,17 @@ pub enum Commands {
 
         /// Positional arguments for the call
         /// To enforce value is always a string, send value in quotes e.g. "'1'" or '"true"'
+        /// To send bytes, use bytes:hex:<hex>, bytes:base64:<base64> or bytes:@<file>; quote a value starting with
+        /// "bytes:" to send it as a string
+        /// Lists, dicts and null are given as JSON, e.g. '[1,2,3]', '{"a":1}' or null
         #[arg()]
         args: Vec<String>,
 
+        /// Read args and kwargs from a JSON file, or '-' for stdin. The file holds
+        /// {"args": [...], "kwargs": {...}} or a JSON array of args, and is sent ahead of ARGS and --kwarg
+        #[arg(long, value_name = "FILE", visible_alias = "args-from")]
+        payload: Option<String>,
+
         /// Keyword argument for the call. To enforce value is always a string, send value in quotes e.g."'1'" or '"true"'. (May be provided multiple times)
         #[arg(short = 'k', long = "kwarg", value_name = "KEY=VALUE")]
         kwargs: Vec<String>,
@@ -60,10 +105,39 @@ pub enum Commands {
         #[arg(short = 'o', long = "option", value_name = "KEY=VALUE")]
         options: Vec<String>,
 
+        /// Parse every argument and keyword argument value as a JSON literal instead of guessing its type
+        #[arg(long)]
+        json_args: bool,
+
+        /// Benchmark the procedure: print latency percentiles, calls per second and error
+        /// counts per session and in total instead of the results
+        #[arg(long)]
+        bench: bool,
+
+        /// Give up on a call after this long, e.g. 500ms, 5s or 1m. Also sent to the router as the call timeout
+        #[arg(long, value_name = "DURATION", value_parser = parse_timeout)]
+        timeout: Option<Duration>,
+
         /// Number of times to repeat the call per session
         #[arg(long, default_value_t = 1)]
         repeat: u32,
 
+        /// Send at a steady rate per session, e.g. 500/s, 30/m or 100/h, instead of as fast as possible
+        #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+        rate: Option<f64>,
+
+        /// Increase the rate linearly from zero to --rate over this long, e.g. 30s
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "rate")]
+        ramp_up: Option<Duration>,
+
+        /// Keep sending for this long, e.g. 60s or 10m. Overrides --repeat
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        duration: Option<Duration>,
+
+        /// Maximum number of outstanding calls per session. Results are still printed in order
+        #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        inflight: u32,
+
         /// Number of parallel sessions to create
         #[arg(long, default_value_t = 1)]
         parallel: u32,
@@ -76,11 +150,52 @@ pub enum Commands {
     Register {
         /// Procedure to register
         procedure: String,
+
+        /// Reconnect with exponential backoff when the connection to the router is lost
+        #[arg(long)]
+        reconnect: bool,
+
+        /// Maximum number of reconnect attempts (unlimited if not set)
+        #[arg(long, value_name = "N", requires = "reconnect")]
+        max_reconnect_attempts: Option<u32>,
+
+        /// Return a fixed result instead of echoing the invocation: a JSON array of args,
+        /// or {"args": [...], "kwargs": {...}}
+        #[arg(long = "return", value_name = "JSON")]
+        return_payload: Option<String>,
+
+        /// Keyword argument to return instead of echoing the invocation (May be provided multiple times)
+        #[arg(long = "return-kwarg", value_name = "KEY=VALUE")]
+        return_kwargs: Vec<String>,
     },
     /// Subscribe to a topic
     Subscribe {
-        /// Topic to subscribe to
-        topic: String,
+        /// Topics to subscribe to, all on the same session
+        #[arg(required_unless_present = "topics_file")]
+        topics: Vec<String>,
+
+        /// Read more topics from FILE, one per line. Blank lines and lines starting with # are skipped
+        #[arg(long, value_name = "FILE")]
+        topics_file: Option<String>,
+
+        /// Print event details with each event: the topic, the publication id and the
+        /// publisher's session, authid and authrole when disclosed
+        #[arg(long)]
+        details: bool,
+
+        /// Only handle events matching a jq-style predicate, e.g. '.kwargs.level == "error"'.
+        /// Supports .args[N], .kwargs.KEY and .details.KEY paths, == != < <= > >=, and, or, not
+        #[arg(long, value_name = "EXPR", value_parser = Filter::parse)]
+        filter: Option<Filter>,
+
+        /// Exit after receiving N events (counted across all sessions)
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        count: Option<u64>,
+
+        /// Stop listening after this long, e.g. 5s. With --count, exit with an error if fewer
+        /// than N events arrived
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        timeout: Option<Duration>,
 
         /// Number of parallel sessions to create
         #[arg(long, default_value_t = 1)]
@@ -89,6 +204,18 @@ pub enum Commands {
         /// Maximum number of concurrent sessions
         #[arg(long, default_value_t = 1)]
         concurrency: usize,
+
+        /// Reconnect with exponential backoff when the connection to the router is lost
+        #[arg(long)]
+        reconnect: bool,
+
+        /// Maximum number of reconnect attempts (unlimited if not set)
+        #[arg(long, value_name = "N", requires = "reconnect")]
+        max_reconnect_attempts: Option<u32>,
+
+        /// Save received events to FILE as newline-delimited JSON, for `wick replay`
+        #[arg(long, value_name = "FILE")]
+        record: Option<String>,
     },
     /// Publish to a topic
     Publish {
@@ -97,9 +224,17 @@ pub enum Commands {
 
         /// Positional arguments for the publish
         /// To enforce value is always a string, send value in quotes e.g. "'1'" or '"true"'
+        /// To send bytes, use bytes:hex:<hex>, bytes:base64:<base64> or bytes:@<file>; quote a value starting with
+        /// "bytes:" to send it as a string
+        /// Lists, dicts and null are given as JSON, e.g. '[1,2,3]', '{"a":1}' or null
         #[arg()]
         args: Vec<String>,
 
+        /// Read args and kwargs from a JSON file, or '-' for stdin. The file holds
+        /// {"args": [...], "kwargs": {...}} or a JSON array of args, and is sent ahead of ARGS and --kwarg
+        #[arg(long, value_name = "FILE", visible_alias = "args-from")]
+        payload: Option<String>,
+
         /// Keyword argument for the publish. To enforce value is always a string, send value in quotes e.g."'1'" or '"true"'. (May be provided multiple times)
         #[arg(short = 'k', long = "kwarg", value_name = "KEY=VALUE")]
         kwargs: Vec<String>,
@@ -108,10 +243,26 @@ pub enum Commands {
         #[arg(short = 'o', long = "option", value_name = "KEY=VALUE")]
         options: Vec<String>,
 
+        /// Parse every argument and keyword argument value as a JSON literal instead of guessing its type
+        #[arg(long)]
+        json_args: bool,
+
         /// Number of times to repeat the publish per session
         #[arg(long, default_value_t = 1)]
         repeat: u32,
 
+        /// Send at a steady rate per session, e.g. 500/s, 30/m or 100/h, instead of as fast as possible
+        #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+        rate: Option<f64>,
+
+        /// Increase the rate linearly from zero to --rate over this long, e.g. 30s
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "rate")]
+        ramp_up: Option<Duration>,
+
+        /// Keep sending for this long, e.g. 60s or 10m. Overrides --repeat
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        duration: Option<Duration>,
+
         /// Number of parallel sessions to create
         #[arg(long, default_value_t = 1)]
         parallel: u32,
@@ -123,6 +274,27 @@ pub enum Commands {
         /// Request acknowledgement from the broker
         #[arg(long)]
         acknowledge: bool,
+
+        /// Publish one event per line read from stdin until EOF, with the line as a string argument
+        #[arg(long, conflicts_with_all = ["payload", "repeat", "rate", "duration", "parallel"])]
+        stdin: bool,
+
+        /// Like --stdin, but parse each line as {"args": [...], "kwargs": {...}} or a JSON array
+        #[arg(long, conflicts_with_all = ["stdin", "payload", "repeat", "rate", "duration", "parallel"])]
+        stdin_json: bool,
+    },
+    /// Republish events saved with `subscribe --record`, keeping their relative timing
+    Replay {
+        /// File written by `subscribe --record`
+        file: String,
+
+        /// Replay faster (e.g. 2 for twice as fast) or slower (e.g. 0.5)
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+
+        /// Request acknowledgement from the broker
+        #[arg(long)]
+        acknowledge: bool,
     },
     /// Generate a WAMP cryptosign ed25519 keypair
     Keygen {
@@ -130,4 +302,32 @@ pub enum Commands {
         #[arg(short = 'O', long = "output-file", value_name = "NAME", num_args = 0..=1, default_missing_value = "key")]
         output_file: Option<String>,
     },
+    /// Manage connection profiles in the config file
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProfileCommand {
+    /// List the names of all profiles
+    List,
+    /// Show the options stored in a profile
+    Show {
+        /// Profile name
+        name: String,
+    },
+    /// Save the connection options given on the command line as a profile, e.g.
+    /// `wick profile add staging --url wss://staging/ws --realm app`.
+    /// Credential files are saved as paths; values from WICK_* variables and prompted secrets are not saved
+    Add {
+        /// Profile name
+        name: String,
+    },
+    /// Remove a profile
+    Remove {
+        /// Profile name
+        name: String,
+    },
 }
@@ -1,127 +1,221 @@
+use crate::bench::BenchStats;
 use crate::colored_eprintln;
-use crate::config::{CallConfig, ConnectionConfig};
-use crate::utils::{
-    CommandOutput, ParsedArg, format_connect_error, parse_arg, wamp_value_to_serde,
-};
-use std::sync::Arc;
+use crate::config::{CallConfig, ConnectionConfig, OutputConfig};
+use crate::error::{Failure, join_sessions};
+use crate::interrupt::Interrupt;
+use crate::output::{format_output, print_error};
+use crate::pacer::Pacer;
+use crate::utils::{CommandOutput, format_connect_error, serde_to_wamp_value, wamp_value_to_serde};
+use futures_util::{StreamExt, stream};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::Semaphore;
-use xconn::sync::CallRequest;
-
-/// Parses a "key=value" string and returns the key and parsed value.
-fn parse_key_value(input: &str) -> Option<(String, ParsedArg)> {
-    let parts: Vec<&str> = input.splitn(2, '=').collect();
-    if parts.len() == 2 {
-        Some((parts[0].to_string(), parse_arg(parts[1])))
-    } else {
-        None
-    }
-}
+use xconn::async_::session::Session;
+use xconn::sync::{CallRequest, CallResponse, Value};
 
 /// Builds a CallRequest from the CallConfig.
 fn build_call_request(config: &CallConfig) -> CallRequest {
     let mut request = CallRequest::new(&config.procedure);
 
+    // Add arguments from --payload, ahead of the ones given on the command line
+    if let Some(ref payload) = config.payload {
+        for arg in &payload.args {
+            request = request.arg(serde_to_wamp_value(arg));
+        }
+        for (key, value) in &payload.kwargs {
+            request = request.kwarg(key, serde_to_wamp_value(value));
+        }
+    }
+
     // Add positional arguments
     for arg in &config.args {
-        request = match parse_arg(arg) {
-            ParsedArg::Integer(v) => request.arg(v),
-            ParsedArg::Float(v) => request.arg(v),
-            ParsedArg::Boolean(v) => request.arg(v),
-            ParsedArg::String(v) => request.arg(v),
-        };
+        request = request.arg(arg.clone());
     }
 
     // Add keyword arguments
-    for kwarg in &config.kwargs {
-        if let Some((key, value)) = parse_key_value(kwarg) {
-            request = match value {
-                ParsedArg::Integer(v) => request.kwarg(&key, v),
-                ParsedArg::Float(v) => request.kwarg(&key, v),
-                ParsedArg::Boolean(v) => request.kwarg(&key, v),
-                ParsedArg::String(v) => request.kwarg(&key, v),
-            };
-        }
+    for (key, value) in &config.kwargs {
+        request = request.kwarg(key, value.clone());
     }
 
     // Add options
-    for opt in &config.options {
-        if let Some((key, value)) = parse_key_value(opt) {
-            request = match value {
-                ParsedArg::Integer(v) => request.option(&key, v),
-                ParsedArg::Float(v) => request.option(&key, v),
-                ParsedArg::Boolean(v) => request.option(&key, v),
-                ParsedArg::String(v) => request.option(&key, v),
-            };
-        }
+    for (key, value) in &config.options {
+        request = request.option(key, value.clone());
+    }
+
+    // Let the router enforce the timeout as well
+    if let Some(timeout) = config.timeout {
+        request = request.option("timeout", timeout.as_millis() as i64);
     }
 
     request
 }
 
+/// Prints a call result in the active output format.
+fn print_result(result: &CallResponse, output_config: OutputConfig) {
+    let bytes_format = output_config.bytes_format;
+    let output = CommandOutput {
+        args: result
+            .args
+            .as_ref()
+            .map(|a: &Vec<Value>| {
+                a.iter()
+                    .map(|v| wamp_value_to_serde(v, bytes_format))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        kwargs: result
+            .kwargs
+            .as_ref()
+            .map(|kw: &HashMap<String, Value>| {
+                kw.iter()
+                    .map(|(k, v)| (k.clone(), wamp_value_to_serde(v, bytes_format)))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+
+    match format_output(&output, output_config.format) {
+        Ok(text) => println!("{}", text),
+        Err(e) => colored_eprintln!("Error serializing result: {}", e),
+    }
+}
+
+/// What became of a single call.
+enum Attempt {
+    /// The router answered, with a result or a WAMP error, after the given latency
+    Done(Box<CallResponse>, Duration),
+    /// No answer within --timeout
+    TimedOut,
+    /// The call could not be sent or the connection was lost
+    Failed(String),
+}
+
+/// Builds and sends one call, enforcing --timeout on the client side.
+async fn attempt_call(session: &Session, call_config: &CallConfig) -> Attempt {
+    let started = Instant::now();
+    let call = session.call(build_call_request(call_config));
+    let result = match call_config.timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, call).await {
+            Ok(result) => result,
+            Err(_) => return Attempt::TimedOut,
+        },
+        None => call.await,
+    };
+
+    match result {
+        Ok(result) => Attempt::Done(Box::new(result), started.elapsed()),
+        Err(e) => Attempt::Failed(e.to_string()),
+    }
+}
+
 /// Executes calls for a single session: connects, runs repeated calls, and disconnects.
+/// With --bench, results are not printed; latencies are recorded and added to `bench_total`.
 async fn run_session(
     conn_config: Arc<ConnectionConfig>,
     call_config: Arc<CallConfig>,
     session_id: u32,
-) {
-    let session = match conn_config.connect().await {
-        Ok(s) => s,
-        Err(e) => {
-            colored_eprintln!(
-                "{}",
-                format_connect_error(session_id, call_config.parallel, e.as_ref())
-            );
-            return;
-        }
+    bench_total: Arc<Mutex<BenchStats>>,
+    interrupt: Interrupt,
+) -> Result<(), Failure> {
+    let session = tokio::select! {
+        result = conn_config.connect() => match result {
+            Ok(s) => s,
+            Err(e) => {
+                colored_eprintln!(
+                    "{}",
+                    format_connect_error(session_id, call_config.parallel, e.as_ref())
+                );
+                return Err(Failure::from_connect_error(e.as_ref()));
+            }
+        },
+        _ = interrupt.wait() => return Err(Failure::Interrupted),
     };
 
-    for iteration in 1..=call_config.repeat {
-        let request = build_call_request(&call_config);
+    let mut outcome = Ok(());
+    let mut stats = BenchStats::default();
+    let started = Instant::now();
+
+    let pacer = Pacer::new(
+        call_config.repeat,
+        call_config.rate,
+        call_config.ramp_up,
+        call_config.duration,
+    );
+    let iterations = stream::unfold(pacer, |mut pacer| async move {
+        pacer.next().await.map(|iteration| (iteration, pacer))
+    });
+
+    // Keep up to --inflight calls outstanding; results still arrive in iteration order
+    let calls = iterations
+        .map(|iteration| {
+            let (session, call_config) = (&session, &*call_config);
+            async move { (iteration, attempt_call(session, call_config).await) }
+        })
+        .buffered(call_config.inflight as usize);
+    let mut calls = std::pin::pin!(calls);
+
+    loop {
+        let (iteration, attempt) = tokio::select! {
+            next = calls.next() => match next {
+                Some(next) => next,
+                None => break,
+            },
+            // Dropping the stream abandons the calls still in flight
+            _ = interrupt.wait() => {
+                outcome = Err(Failure::Interrupted);
+                break;
+            }
+        };
 
-        match session.call(request).await {
-            Ok(result) => {
+        match attempt {
+            Attempt::Done(result, latency) => {
                 if let Some(err) = result.error {
-                    colored_eprintln!("{}", err.uri);
+                    outcome = Err(Failure::Application);
+                    if call_config.bench {
+                        stats.record_error();
+                        continue;
+                    }
+                    print_error(&err, call_config.output);
                     break;
                 }
 
-                let output = CommandOutput {
-                    args: result
-                        .args
-                        .as_ref()
-                        .map(|a: &Vec<xconn::sync::Value>| {
-                            a.iter().map(wamp_value_to_serde).collect()
-                        })
-                        .unwrap_or_default(),
-                    kwargs: result
-                        .kwargs
-                        .as_ref()
-                        .map(
-                            |kw: &std::collections::HashMap<String, xconn::sync::Value>| {
-                                kw.iter()
-                                    .map(|(k, v)| (k.clone(), wamp_value_to_serde(v)))
-                                    .collect()
-                            },
-                        )
-                        .unwrap_or_default(),
-                };
-                match serde_json::to_string_pretty(&output) {
-                    Ok(json) => println!("{}", json),
-                    Err(e) => colored_eprintln!(
-                        "Session {} Iteration {} Error serializing result: {}",
-                        session_id,
-                        iteration,
-                        e
-                    ),
+                if call_config.bench {
+                    stats.record(latency);
+                    continue;
                 }
+
+                print_result(&result, call_config.output);
             }
-            Err(e) => {
+            // Only this call is abandoned; the router cancels it through the timeout option,
+            // while the session's other in-flight calls keep running
+            Attempt::TimedOut => {
+                outcome = Err(Failure::Timeout);
+                if call_config.bench {
+                    stats.record_error();
+                    continue;
+                }
+                colored_eprintln!(
+                    "Session {} Iteration {} Call timed out after {:?}",
+                    session_id,
+                    iteration,
+                    call_config.timeout.unwrap_or_default()
+                );
+                break;
+            }
+            Attempt::Failed(e) => {
                 colored_eprintln!(
                     "Session {} Iteration {} Call Error: {}",
                     session_id,
                     iteration,
                     e
                 );
+                outcome = Err(Failure::Connection);
+                // The session can't make further calls, but the lost call still counts
+                if call_config.bench {
+                    stats.record_error();
+                }
                 break;
             }
         }
@@ -130,35 +224,64 @@ async fn run_session(
     if let Err(e) = session.leave().await {
         colored_eprintln!("Session {} Error leaving: {}", session_id, e);
     }
+
+    if call_config.bench {
+        stats.set_elapsed(started.elapsed());
+        if call_config.parallel > 1 {
+            println!("{}", stats.report(&format!("Session {}: ", session_id)));
+        }
+        bench_total.lock().unwrap().merge(&stats);
+    }
+
+    outcome
 }
 
-pub async fn handle(
-    conn_config: ConnectionConfig,
-    call_config: CallConfig,
-) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn handle(conn_config: ConnectionConfig, call_config: CallConfig) -> Result<(), Failure> {
     let semaphore = Arc::new(Semaphore::new(call_config.concurrency));
     let conn_config = Arc::new(conn_config);
     let call_config = Arc::new(call_config);
 
+    let bench_total = Arc::new(Mutex::new(BenchStats::default()));
+    let started = Instant::now();
+    let interrupt = Interrupt::listen();
+
     let mut handles = Vec::with_capacity(call_config.parallel as usize);
 
     for session_id in 1..=call_config.parallel {
-        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        // Sessions waiting for a permit are not started after Ctrl+C
+        let permit = tokio::select! {
+            permit = semaphore.clone().acquire_owned() => permit.unwrap(),
+            _ = interrupt.wait() => break,
+        };
 
         let conn_config = conn_config.clone();
         let call_config = call_config.clone();
+        let bench_total = bench_total.clone();
+        let interrupt = interrupt.clone();
 
         let handle = tokio::spawn(async move {
             let _permit = permit;
-            run_session(conn_config, call_config, session_id).await;
+            run_session(conn_config, call_config, session_id, bench_total, interrupt).await
         });
 
         handles.push(handle);
     }
 
-    for handle in handles {
-        let _ = handle.await;
+    let mut outcome = join_sessions(handles).await;
+    if interrupt.is_set() {
+        outcome = Err(Failure::Interrupted);
+    }
+
+    if call_config.bench {
+        let mut total = bench_total.lock().unwrap().clone();
+        total.set_elapsed(started.elapsed());
+        let label = if call_config.parallel > 1 {
+            "Total: "
+        } else {
+            ""
+        };
+        println!("{}", total.report(label));
     }
 
-    Ok(())
+    outcome
 }
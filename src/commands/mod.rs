@@ -1,5 +1,7 @@
 pub mod call;
 pub mod keygen;
+pub mod profile;
 pub mod publish;
 pub mod register;
+pub mod replay;
 pub mod subscribe;
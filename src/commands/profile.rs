@@ -0,0 +1,40 @@
+use crate::cli::{Cli, ProfileCommand};
+use crate::profile::{Profile, ProfileFile};
+use clap::ArgMatches;
+
+pub fn handle(
+    cli: &Cli,
+    matches: &ArgMatches,
+    command: &ProfileCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = ProfileFile::path(cli.config.as_deref())?;
+    let mut file = ProfileFile::load(&path)?;
+
+    match command {
+        ProfileCommand::List => {
+            for name in file.profiles.keys() {
+                println!("{}", name);
+            }
+        }
+        ProfileCommand::Show { name } => {
+            let profile = file.get(name)?.masked();
+            print!("{}", toml::to_string_pretty(&profile)?);
+        }
+        ProfileCommand::Add { name } => {
+            let profile = Profile::from_command_line(cli, matches);
+            let existed = file.profiles.insert(name.clone(), profile).is_some();
+            file.save(&path)?;
+            let action = if existed { "Updated" } else { "Added" };
+            println!("{} profile '{}' in {}", action, name, path.display());
+        }
+        ProfileCommand::Remove { name } => {
+            if file.profiles.remove(name).is_none() {
+                return Err(format!("Profile '{}' not found", name).into());
+            }
+            file.save(&path)?;
+            println!("Removed profile '{}' from {}", name, path.display());
+        }
+    }
+
+    Ok(())
+}
@@ -1,64 +1,177 @@
 use crate::colored_eprintln;
-use crate::config::{ConnectionConfig, PublishConfig};
-use crate::utils::{ParsedArg, format_connect_error, parse_arg};
+use crate::config::{ConnectionConfig, OutputConfig, PublishConfig};
+use crate::error::{Failure, join_sessions};
+use crate::output::print_error;
+use crate::pacer::Pacer;
+use crate::utils::{Payload, format_connect_error, serde_to_wamp_value};
+use serde_json::Value as SerdeValue;
 use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::sync::Semaphore;
-use xconn::sync::PublishRequest;
-
-/// Parses a "key=value" string and returns the key and parsed value.
-fn parse_key_value(input: &str) -> Option<(String, ParsedArg)> {
-    let parts: Vec<&str> = input.splitn(2, '=').collect();
-    if parts.len() == 2 {
-        Some((parts[0].to_string(), parse_arg(parts[1])))
-    } else {
-        None
+use xconn::async_::session::Session;
+use xconn::sync::{PublishRequest, Value};
+
+/// Builds a PublishRequest for `topic` with the given arguments, asking the router to
+/// acknowledge it if `acknowledge` is set.
+pub fn publish_request(
+    topic: &str,
+    args: impl IntoIterator<Item = Value>,
+    kwargs: impl IntoIterator<Item = (String, Value)>,
+    acknowledge: bool,
+) -> PublishRequest {
+    let mut request = PublishRequest::new(topic);
+    for arg in args {
+        request = request.arg(arg);
     }
+    for (key, value) in kwargs {
+        request = request.kwarg(&key, value);
+    }
+    if acknowledge {
+        request = request.option("acknowledge", true);
+    }
+    request
 }
 
-/// Builds a PublishRequest from the PublishConfig.
-fn build_publish_request(config: &PublishConfig) -> PublishRequest {
-    let mut request = PublishRequest::new(&config.topic);
-
-    // Add positional arguments
-    for arg in &config.args {
-        request = match parse_arg(arg) {
-            ParsedArg::Integer(v) => request.arg(v),
-            ParsedArg::Float(v) => request.arg(v),
-            ParsedArg::Boolean(v) => request.arg(v),
-            ParsedArg::String(v) => request.arg(v),
-        };
+/// Builds a PublishRequest from the PublishConfig. `payload` is sent ahead of the
+/// arguments given on the command line; it comes from --payload or a line of stdin.
+fn build_publish_request(config: &PublishConfig, payload: Option<&Payload>) -> PublishRequest {
+    let payload_args = payload.into_iter().flat_map(|p| &p.args);
+    let payload_kwargs = payload.into_iter().flat_map(|p| &p.kwargs);
+
+    let args = payload_args
+        .map(serde_to_wamp_value)
+        .chain(config.args.iter().cloned());
+    let kwargs = payload_kwargs
+        .map(|(key, value)| (key.clone(), serde_to_wamp_value(value)))
+        .chain(config.kwargs.iter().cloned());
+    let mut request = publish_request(&config.topic, args, kwargs, config.acknowledge);
+
+    for (key, value) in &config.options {
+        request = request.option(key, value.clone());
     }
 
-    // Add keyword arguments
-    for kwarg in &config.kwargs {
-        if let Some((key, value)) = parse_key_value(kwarg) {
-            request = match value {
-                ParsedArg::Integer(v) => request.kwarg(&key, v),
-                ParsedArg::Float(v) => request.kwarg(&key, v),
-                ParsedArg::Boolean(v) => request.kwarg(&key, v),
-                ParsedArg::String(v) => request.kwarg(&key, v),
-            };
+    request
+}
+
+/// Publishes a request and reports a failed publish.
+pub async fn send(
+    session: &Session,
+    request: PublishRequest,
+    output: OutputConfig,
+    session_id: u32,
+    iteration: u32,
+) -> Result<(), Failure> {
+    match session.publish(request).await {
+        Ok(response) => {
+            if let Some(resp) = response
+                && let Some(err) = resp.error
+            {
+                print_error(&err, output);
+                return Err(Failure::Application);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            colored_eprintln!(
+                "Session {} Iteration {} Publish Error: {}",
+                session_id,
+                iteration,
+                e
+            );
+            Err(Failure::Connection)
         }
     }
+}
 
-    // Add options
-    for opt in &config.options {
-        if let Some((key, value)) = parse_key_value(opt) {
-            request = match value {
-                ParsedArg::Integer(v) => request.option(&key, v),
-                ParsedArg::Float(v) => request.option(&key, v),
-                ParsedArg::Boolean(v) => request.option(&key, v),
-                ParsedArg::String(v) => request.option(&key, v),
-            };
-        }
+/// Publishes --repeat times, or for --duration, paced by --rate.
+async fn publish_repeated(
+    session: &Session,
+    publish_config: &PublishConfig,
+    session_id: u32,
+) -> Result<(), Failure> {
+    let mut pacer = Pacer::new(
+        publish_config.repeat,
+        publish_config.rate,
+        publish_config.ramp_up,
+        publish_config.duration,
+    );
+
+    while let Some(iteration) = pacer.next().await {
+        let request = build_publish_request(publish_config, publish_config.payload.as_ref());
+        send(
+            session,
+            request,
+            publish_config.output,
+            session_id,
+            iteration,
+        )
+        .await?;
     }
 
-    // Add acknowledge option if requested
-    if config.acknowledge {
-        request = request.option("acknowledge", true);
+    Ok(())
+}
+
+/// Publishes one event per line read from stdin until EOF. Each line is sent as a string
+/// argument, or with --stdin-json parsed as {"args": [...], "kwargs": {...}} or a JSON array.
+async fn publish_lines(
+    session: &Session,
+    publish_config: &PublishConfig,
+    session_id: u32,
+) -> Result<(), Failure> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut outcome = Ok(());
+    let mut line_number = 0;
+
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                colored_eprintln!("Failed to read from stdin: {}", e);
+                return Err(Failure::Usage);
+            }
+        };
+        line_number += 1;
+
+        let payload = if publish_config.stdin_json {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Payload::parse(&line) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    // Skip the line but keep reading, as the input is usually a live stream
+                    colored_eprintln!("Line {}: {}", line_number, e);
+                    outcome = Err(Failure::Usage);
+                    continue;
+                }
+            }
+        } else {
+            Payload {
+                args: vec![SerdeValue::String(line)],
+                ..Default::default()
+            }
+        };
+
+        let request = build_publish_request(publish_config, Some(&payload));
+        match send(
+            session,
+            request,
+            publish_config.output,
+            session_id,
+            line_number,
+        )
+        .await
+        {
+            Ok(()) => {}
+            // Without a connection no further line can be published
+            Err(Failure::Connection) => return Err(Failure::Connection),
+            // A rejected publish is reported like a bad line and the stream goes on
+            Err(failure) => outcome = Err(failure),
+        }
     }
 
-    request
+    outcome
 }
 
 /// Executes publishes for a single session: connects, runs repeated publishes, and disconnects.
@@ -66,7 +179,7 @@ async fn run_session(
     conn_config: Arc<ConnectionConfig>,
     publish_config: Arc<PublishConfig>,
     session_id: u32,
-) {
+) -> Result<(), Failure> {
     let session = match conn_config.connect().await {
         Ok(s) => s,
         Err(e) => {
@@ -74,43 +187,27 @@ async fn run_session(
                 "{}",
                 format_connect_error(session_id, publish_config.parallel, e.as_ref())
             );
-            return;
+            return Err(Failure::from_connect_error(e.as_ref()));
         }
     };
 
-    for iteration in 1..=publish_config.repeat {
-        let request = build_publish_request(&publish_config);
-
-        match session.publish(request).await {
-            Ok(response) => {
-                if let Some(resp) = response
-                    && let Some(err) = resp.error
-                {
-                    colored_eprintln!("{}", err.uri);
-                    break;
-                }
-            }
-            Err(e) => {
-                colored_eprintln!(
-                    "Session {} Iteration {} Publish Error: {}",
-                    session_id,
-                    iteration,
-                    e
-                );
-                break;
-            }
-        }
-    }
+    let outcome = if publish_config.stdin {
+        publish_lines(&session, &publish_config, session_id).await
+    } else {
+        publish_repeated(&session, &publish_config, session_id).await
+    };
 
     if let Err(e) = session.leave().await {
         eprintln!("Session {} Error leaving: {}", session_id, e);
     }
+
+    outcome
 }
 
 pub async fn handle(
     conn_config: ConnectionConfig,
     publish_config: PublishConfig,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), Failure> {
     let semaphore = Arc::new(Semaphore::new(publish_config.concurrency));
     let conn_config = Arc::new(conn_config);
     let publish_config = Arc::new(publish_config);
@@ -125,15 +222,11 @@ pub async fn handle(
 
         let handle = tokio::spawn(async move {
             let _permit = permit;
-            run_session(conn_config, publish_config, session_id).await;
+            run_session(conn_config, publish_config, session_id).await
         });
 
         handles.push(handle);
     }
 
-    for handle in handles {
-        let _ = handle.await;
-    }
-
-    Ok(())
+    join_sessions(handles).await
 }
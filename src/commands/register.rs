@@ -1,67 +1,136 @@
-use crate::colored_eprintln;
 use crate::colored_println;
-use crate::config::ConnectionConfig;
-use crate::utils::{CommandOutput, format_connect_error, wamp_async_value_to_serde};
-use tokio::signal;
-use xconn::async_::{Invocation, RegisterRequest, Yield};
+use crate::config::{ConnectionConfig, OutputConfig, RegisterConfig};
+use crate::error::{CommandError, Failure};
+use crate::interrupt::Interrupt;
+use crate::output::format_output;
+use crate::reconnect::{SessionEnd, keep_connected};
+use crate::utils::{
+    CommandOutput, format_connect_error, serde_to_wamp_value, wamp_async_value_to_serde,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use xconn::async_::session::Session;
+use xconn::async_::{Invocation, RegisterRequest, Value, Yield};
 
-async fn registration_handler(inv: Invocation) -> Yield {
+/// What the registered procedure answers with.
+enum Reply {
+    /// Echo the invocation's args and kwargs back
+    Echo,
+    /// Return the payload given with --return and --return-kwarg
+    Result(Vec<Value>, HashMap<String, Value>),
+}
+
+impl Reply {
+    fn from_config(config: &RegisterConfig) -> Self {
+        if config.return_payload.is_none() && config.return_kwargs.is_empty() {
+            return Reply::Echo;
+        }
+
+        let mut args = Vec::new();
+        let mut kwargs = HashMap::new();
+        if let Some(ref payload) = config.return_payload {
+            args.extend(payload.args.iter().map(serde_to_wamp_value));
+            kwargs.extend(
+                payload
+                    .kwargs
+                    .iter()
+                    .map(|(k, v)| (k.clone(), serde_to_wamp_value(v))),
+            );
+        }
+        kwargs.extend(config.return_kwargs.iter().cloned());
+        Reply::Result(args, kwargs)
+    }
+}
+
+async fn registration_handler(
+    inv: Invocation,
+    output_config: OutputConfig,
+    reply: Arc<Reply>,
+) -> Yield {
+    let bytes_format = output_config.bytes_format;
     let output = CommandOutput {
-        args: inv.args.iter().map(wamp_async_value_to_serde).collect(),
+        args: inv
+            .args
+            .iter()
+            .map(|v| wamp_async_value_to_serde(v, bytes_format))
+            .collect(),
         kwargs: inv
             .kwargs
             .iter()
-            .map(|(k, v): (_, _)| (k.clone(), wamp_async_value_to_serde(v)))
+            .map(|(k, v): (_, _)| (k.clone(), wamp_async_value_to_serde(v, bytes_format)))
             .collect(),
+        ..Default::default()
     };
 
-    match serde_json::to_string_pretty(&output) {
-        Ok(json) => println!("{}", json),
+    match format_output(&output, output_config.format) {
+        Ok(text) => println!("{}", text),
         Err(e) => println!("Error serializing invocation: {}", e),
     }
 
-    Yield::new(inv.args, inv.kwargs)
+    match reply.as_ref() {
+        Reply::Echo => Yield::new(inv.args, inv.kwargs),
+        Reply::Result(args, kwargs) => Yield::new(args.clone(), kwargs.clone()),
+    }
 }
 
-pub async fn handle(
-    conn_config: ConnectionConfig,
-    procedure: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let session = match conn_config.connect().await {
-        Ok(s) => s,
-        Err(e) => {
-            colored_eprintln!("{}", format_connect_error(1, 1, e.as_ref()));
-            return Ok(());
-        }
-    };
+/// Connects to the router and registers the procedure.
+async fn connect_and_register(
+    conn_config: &ConnectionConfig,
+    register_config: &RegisterConfig,
+    reply: Arc<Reply>,
+) -> Result<Session, CommandError> {
+    let session = conn_config.connect().await.map_err(|e| {
+        CommandError::new(
+            Failure::from_connect_error(e.as_ref()),
+            format_connect_error(1, 1, e.as_ref()),
+        )
+    })?;
 
-    let register_request = RegisterRequest::new(procedure, registration_handler);
+    let output_config = register_config.output;
+    let register_request = RegisterRequest::new(&register_config.procedure, move |inv| {
+        registration_handler(inv, output_config, reply.clone())
+    });
 
-    match session.register(register_request).await {
-        Ok(resp) => {
-            if let Some(err) = resp.error {
-                colored_eprintln!("{}", err.uri);
-                return Ok(());
+    let error = match session.register(register_request).await {
+        Ok(resp) => match resp.error {
+            Some(err) => CommandError::wamp(&err, output_config),
+            None => {
+                colored_println!("Registered procedure '{}'", register_config.procedure);
+                return Ok(session);
             }
-            colored_println!("Registered procedure '{}'", procedure);
-        }
-        Err(e) => {
-            colored_eprintln!("Error registering procedure: {}", e);
-            return Ok(());
-        }
-    }
+        },
+        Err(e) => CommandError::new(
+            Failure::Connection,
+            format!("Error registering procedure: {}", e),
+        ),
+    };
 
-    colored_println!("Press Ctrl+C to exit");
-    tokio::select! {
-        _ = signal::ctrl_c() => {
+    let _ = session.leave().await;
+    Err(error)
+}
+
+pub async fn handle(
+    conn_config: ConnectionConfig,
+    register_config: RegisterConfig,
+) -> Result<(), Failure> {
+    let reply = Arc::new(Reply::from_config(&register_config));
+    let interrupt = Interrupt::listen();
+
+    let end = keep_connected(
+        register_config.reconnect,
+        register_config.max_reconnect_attempts,
+        "",
+        || connect_and_register(&conn_config, &register_config, reply.clone()),
+        || interrupt.wait(),
+        || colored_println!("Press Ctrl+C to exit"),
+    )
+    .await;
+
+    match end {
+        SessionEnd::Stopped => {
             colored_println!("Exiting...");
+            Ok(())
         }
-        _ = session.wait_disconnect() => {
-            colored_eprintln!("Lost connection to router");
-        }
+        SessionEnd::NotConnected(failure) | SessionEnd::Lost(failure) => Err(failure),
     }
-
-    let _ = session.leave().await;
-
-    Ok(())
 }
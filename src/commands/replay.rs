@@ -0,0 +1,69 @@
+use crate::colored_eprintln;
+use crate::colored_println;
+use crate::commands::publish::{publish_request, send};
+use crate::config::{ConnectionConfig, ReplayConfig};
+use crate::error::Failure;
+use crate::record;
+use crate::utils::format_connect_error;
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub async fn handle(
+    conn_config: ConnectionConfig,
+    replay_config: ReplayConfig,
+) -> Result<(), Failure> {
+    if !(replay_config.speed.is_finite() && replay_config.speed > 0.0) {
+        colored_eprintln!("--speed must be greater than 0");
+        return Err(Failure::Usage);
+    }
+
+    let events = record::load(&replay_config.file).map_err(|e| {
+        colored_eprintln!("{}", e);
+        Failure::Usage
+    })?;
+
+    let session = match conn_config.connect().await {
+        Ok(s) => s,
+        Err(e) => {
+            colored_eprintln!("{}", format_connect_error(1, 1, e.as_ref()));
+            return Err(Failure::from_connect_error(e.as_ref()));
+        }
+    };
+
+    let count = events.len();
+    let started = Instant::now();
+    let first_timestamp = events.first().map(|event| event.timestamp).unwrap_or(0);
+    let mut outcome = Ok(());
+
+    for (index, event) in events.into_iter().enumerate() {
+        // Keep the original spacing between events, scaled by --speed
+        let offset = event.timestamp.saturating_sub(first_timestamp);
+        let delay = Duration::from_millis(offset).div_f64(replay_config.speed);
+        tokio::time::sleep_until(started + delay).await;
+
+        let request = publish_request(
+            &event.topic,
+            event.args.iter().map(record::decode_value),
+            event
+                .kwargs
+                .iter()
+                .map(|(k, v)| (k.clone(), record::decode_value(v))),
+            replay_config.acknowledge,
+        );
+        let iteration = index as u32 + 1;
+        if let Err(failure) = send(&session, request, replay_config.output, 1, iteration).await {
+            outcome = Err(failure);
+            break;
+        }
+    }
+
+    if outcome.is_ok() {
+        colored_println!("Replayed {} events from '{}'", count, replay_config.file);
+    }
+
+    if let Err(e) = session.leave().await {
+        colored_eprintln!("Error leaving: {}", e);
+    }
+
+    outcome
+}
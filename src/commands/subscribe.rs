@@ -1,106 +1,288 @@
 use crate::colored_eprintln;
 use crate::colored_println;
-use crate::config::{ConnectionConfig, SubscribeConfig};
-use crate::utils::{CommandOutput, format_connect_error, wamp_async_value_to_serde};
-use std::sync::Arc;
+use crate::config::{ConnectionConfig, OutputConfig, SubscribeConfig};
+use crate::error::{CommandError, Failure, join_sessions};
+use crate::filter::Filter;
+use crate::interrupt::Interrupt;
+use crate::output::format_output;
+use crate::reconnect::{SessionEnd, keep_connected};
+use crate::record::{RecordedEvent, Recorder};
+use crate::utils::{BytesFormat, CommandOutput, format_connect_error, wamp_async_value_to_serde};
+use serde_json::Value as SerdeValue;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::signal;
-use tokio::sync::Semaphore;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, Semaphore};
+use xconn::async_::session::Session;
 use xconn::async_::{Event, SubscribeRequest};
 
-/// Builds a SubscribeRequest from the SubscribeConfig.
-fn build_subscribe_request(config: &SubscribeConfig) -> SubscribeRequest {
+/// Stops printing once --count events have arrived and signals that the command is done.
+/// Events are counted by publication id: with --parallel every session receives the same
+/// publication, which counts once.
+struct EventLimit {
+    count: u64,
+    received: Mutex<HashSet<i64>>,
+    reached: Notify,
+}
+
+impl EventLimit {
+    fn new(count: u64) -> Self {
+        Self {
+            count,
+            received: Mutex::new(HashSet::new()),
+            reached: Notify::new(),
+        }
+    }
+
+    /// Counts an event. Returns false for new publications beyond the limit, which are dropped.
+    fn accept(&self, publication_id: i64) -> bool {
+        let mut received = self.received.lock().unwrap();
+        if received.contains(&publication_id) {
+            return true;
+        }
+        if received.len() as u64 >= self.count {
+            return false;
+        }
+        received.insert(publication_id);
+        if received.len() as u64 == self.count {
+            self.reached.notify_waiters();
+        }
+        true
+    }
+
+    fn received(&self) -> u64 {
+        self.received.lock().unwrap().len() as u64
+    }
+
+    /// Waits until --count events have arrived, returning at once if they already have.
+    async fn wait(&self) {
+        let notified = self.reached.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.received() < self.count {
+            notified.await;
+        }
+    }
+}
+
+/// State shared by the event handlers of all sessions.
+struct SharedState {
+    filter: Option<Filter>,
+    recorder: Option<Recorder>,
+    limit: Option<EventLimit>,
+}
+
+/// Builds a SubscribeRequest for one of the topics of the SubscribeConfig.
+fn build_subscribe_request(
+    config: &SubscribeConfig,
+    topic: &str,
+    shared: Arc<SharedState>,
+) -> SubscribeRequest {
     // Note: SubscribeRequest doesn't support options via builder pattern
     // Options would need to be added at the xconn-rust library level
-    SubscribeRequest::new(&config.topic, event_handler)
+    let output_config = config.output;
+    let subscription = topic.to_string();
+    let details = config.details;
+    // Label events with their subscription when there is more than one
+    let labelled = config.topics.len() > 1;
+    SubscribeRequest::new(topic, move |event| {
+        event_handler(
+            event,
+            output_config,
+            subscription.clone(),
+            labelled,
+            details,
+            shared.clone(),
+        )
+    })
 }
 
-async fn event_handler(event: Event) {
-    let output = CommandOutput {
-        args: event.args.iter().map(wamp_async_value_to_serde).collect(),
+async fn event_handler(
+    event: Event,
+    output_config: OutputConfig,
+    subscription: String,
+    labelled: bool,
+    details: bool,
+    shared: Arc<SharedState>,
+) {
+    let bytes_format = output_config.bytes_format;
+    let metadata = (details || shared.filter.is_some())
+        .then(|| event_details(&event, &subscription, bytes_format));
+    let mut output = CommandOutput {
+        args: event
+            .args
+            .iter()
+            .map(|v| wamp_async_value_to_serde(v, bytes_format))
+            .collect(),
         kwargs: event
             .kwargs
             .iter()
-            .map(|(k, v): (_, _)| (k.clone(), wamp_async_value_to_serde(v)))
+            .map(|(k, v): (_, _)| (k.clone(), wamp_async_value_to_serde(v, bytes_format)))
             .collect(),
+        subscription: labelled.then(|| subscription.clone()),
+        ..Default::default()
     };
 
-    match serde_json::to_string_pretty(&output) {
-        Ok(json) => println!("{}", json),
+    // Filter on the same form that is printed, before the event is counted or recorded
+    if let Some(filter) = &shared.filter {
+        let value = serde_json::json!({
+            "args": output.args,
+            "kwargs": output.kwargs,
+            "details": metadata,
+        });
+        if !filter.matches(&value) {
+            return;
+        }
+    }
+
+    if shared
+        .limit
+        .as_ref()
+        .is_some_and(|limit| !limit.accept(event.publication_id))
+    {
+        return;
+    }
+    if details {
+        output.details = metadata;
+    }
+
+    if let Some(recorder) = &shared.recorder {
+        let recorded = RecordedEvent::new(
+            &subscription,
+            event.publication_id,
+            &event.args,
+            &event.kwargs,
+        );
+        if let Err(e) = recorder.record(&recorded) {
+            colored_eprintln!("{}", e);
+        }
+    }
+
+    match format_output(&output, output_config.format) {
+        Ok(text) => println!("{}", text),
         Err(e) => eprintln!("Error serializing event: {}", e),
     }
 }
 
+/// Collects the event metadata printed with --details: the topic, the publication id and
+/// whatever the router put in the details, such as the disclosed publisher.
+fn event_details(
+    event: &Event,
+    topic: &str,
+    bytes_format: BytesFormat,
+) -> HashMap<String, SerdeValue> {
+    let mut details: HashMap<_, _> = event
+        .details
+        .iter()
+        .map(|(k, v)| (k.clone(), wamp_async_value_to_serde(v, bytes_format)))
+        .collect();
+    details.insert("topic".to_string(), SerdeValue::from(topic));
+    details.insert(
+        "publication".to_string(),
+        SerdeValue::from(event.publication_id),
+    );
+    details
+}
+
+/// Connects to the router and subscribes to every topic.
+async fn connect_and_subscribe(
+    conn_config: &ConnectionConfig,
+    subscribe_config: &SubscribeConfig,
+    shared: Arc<SharedState>,
+    label: &str,
+) -> Result<Session, CommandError> {
+    let session = conn_config.connect().await.map_err(|e| {
+        CommandError::new(
+            Failure::from_connect_error(e.as_ref()),
+            format_connect_error(1, 1, e.as_ref()),
+        )
+    })?;
+
+    for topic in &subscribe_config.topics {
+        let request = build_subscribe_request(subscribe_config, topic, shared.clone());
+        let error = match session.subscribe(request).await {
+            Ok(resp) => match resp.error {
+                Some(err) => CommandError::wamp(&err, subscribe_config.output),
+                None => {
+                    colored_println!("{}Subscribed to topic '{}'", label, topic);
+                    continue;
+                }
+            },
+            Err(e) => CommandError::new(Failure::Connection, format!("Subscribe Error: {}", e)),
+        };
+
+        let _ = session.leave().await;
+        return Err(error);
+    }
+
+    Ok(session)
+}
+
 /// Runs a single subscribe session: connects, subscribes, and waits.
+/// With --reconnect, a lost connection is re-established and the topic re-subscribed.
 async fn run_session(
     conn_config: Arc<ConnectionConfig>,
     subscribe_config: Arc<SubscribeConfig>,
+    shared: Arc<SharedState>,
     session_id: u32,
     shutdown: tokio::sync::watch::Receiver<bool>,
     disconnect_tx: tokio::sync::mpsc::Sender<()>,
     ctrl_c_printed: Arc<AtomicBool>,
-) {
-    let session = match conn_config.connect().await {
-        Ok(s) => s,
-        Err(e) => {
-            colored_eprintln!(
-                "{}",
-                format_connect_error(session_id, subscribe_config.parallel, e.as_ref())
-            );
-            return;
-        }
+) -> Result<(), Failure> {
+    let label = if subscribe_config.parallel > 1 {
+        format!("Session {}: ", session_id)
+    } else {
+        String::new()
     };
 
-    let request = build_subscribe_request(&subscribe_config);
-
-    match session.subscribe(request).await {
-        Ok(resp) => {
-            if let Some(err) = resp.error {
-                colored_eprintln!("{}", err.uri);
-                let _ = session.leave().await;
-                return;
-            }
-
-            if subscribe_config.parallel > 1 {
-                colored_println!(
-                    "Session {}: Subscribed to topic '{}'",
-                    session_id,
-                    subscribe_config.topic
-                );
-            } else {
-                colored_println!("Subscribed to topic '{}'", subscribe_config.topic);
+    let end = keep_connected(
+        subscribe_config.reconnect,
+        subscribe_config.max_reconnect_attempts,
+        &label,
+        || connect_and_subscribe(&conn_config, &subscribe_config, shared.clone(), &label),
+        || {
+            let mut shutdown = shutdown.clone();
+            async move {
+                let _ = shutdown.wait_for(|stop| *stop).await;
             }
-
+        },
+        || {
             // Print "Press Ctrl+C to exit" only once across all sessions
             if !ctrl_c_printed.swap(true, Ordering::Relaxed) {
                 colored_println!("Press Ctrl+C to exit");
             }
-        }
-        Err(e) => {
-            colored_eprintln!("Session {} Subscribe Error: {}", session_id, e);
-            let _ = session.leave().await;
-            return;
-        }
-    }
+        },
+    )
+    .await;
 
-    // Wait for either shutdown signal or connection loss
-    let mut shutdown = shutdown;
-    let disconnected = tokio::select! {
-        _ = shutdown.changed() => false,
-        _ = session.wait_disconnect() => true,
-    };
-
-    if disconnected {
-        let _ = disconnect_tx.send(()).await;
-    } else if let Err(e) = session.leave().await {
-        colored_eprintln!("Session {} Error leaving: {}", session_id, e);
+    match end {
+        SessionEnd::Stopped => Ok(()),
+        SessionEnd::NotConnected(failure) => Err(failure),
+        SessionEnd::Lost(failure) => {
+            let _ = disconnect_tx.send(()).await;
+            Err(failure)
+        }
     }
 }
 
 pub async fn handle(
     conn_config: ConnectionConfig,
     subscribe_config: SubscribeConfig,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), Failure> {
+    let recorder = match subscribe_config.record.as_deref().map(Recorder::create) {
+        Some(Ok(recorder)) => Some(recorder),
+        Some(Err(e)) => {
+            colored_eprintln!("{}", e);
+            return Err(Failure::Usage);
+        }
+        None => None,
+    };
+    let shared = Arc::new(SharedState {
+        filter: subscribe_config.filter.clone(),
+        recorder,
+        limit: subscribe_config.count.map(EventLimit::new),
+    });
+
     let semaphore = Arc::new(Semaphore::new(subscribe_config.concurrency));
     let conn_config = Arc::new(conn_config);
     let subscribe_config = Arc::new(subscribe_config);
@@ -112,13 +294,40 @@ pub async fn handle(
     let (disconnect_tx, mut disconnect_rx) = tokio::sync::mpsc::channel::<()>(1);
 
     let ctrl_c_printed = Arc::new(AtomicBool::new(false));
+    let interrupt = Interrupt::listen();
+
+    // --timeout counts from the start, including the time spent connecting
+    let deadline = subscribe_config
+        .timeout
+        .map(|timeout| tokio::time::Instant::now() + timeout);
+
+    let count_reached = || async {
+        match &shared.limit {
+            Some(limit) => limit.wait().await,
+            None => std::future::pending().await,
+        }
+    };
+    let deadline = || async {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    };
 
     let mut handles = Vec::with_capacity(subscribe_config.parallel as usize);
 
     for session_id in 1..=subscribe_config.parallel {
-        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        // Sessions waiting for a permit are not started once the command is done;
+        // the select below then ends it for the same reason
+        let permit = tokio::select! {
+            permit = semaphore.clone().acquire_owned() => permit.unwrap(),
+            _ = interrupt.wait() => break,
+            _ = count_reached() => break,
+            _ = deadline() => break,
+        };
         let conn_config = conn_config.clone();
         let subscribe_config = subscribe_config.clone();
+        let shared = shared.clone();
         let shutdown_rx = shutdown_rx.clone();
         let disconnect_tx = disconnect_tx.clone();
         let ctrl_c_printed = ctrl_c_printed.clone();
@@ -128,44 +337,61 @@ pub async fn handle(
             run_session(
                 conn_config,
                 subscribe_config,
+                shared,
                 session_id,
                 shutdown_rx,
                 disconnect_tx,
                 ctrl_c_printed,
             )
-            .await;
+            .await
         });
 
         handles.push(handle);
     }
 
     // Spawn a task to track when all sessions finish
-    let mut join_handle = tokio::spawn(async move {
-        for handle in handles {
-            let _ = handle.await;
-        }
-    });
+    let mut join_handle = tokio::spawn(join_sessions(handles));
 
-    tokio::select! {
-        _ = signal::ctrl_c() => {
+    let mut outcome = Ok(());
+    let finished = tokio::select! {
+        // When the spawn loop stopped early no sessions may be left, so the reason it
+        // stopped must win over the finished sessions
+        biased;
+        _ = interrupt.wait() => {
             colored_println!("Exiting...");
+            None
         }
-        _ = disconnect_rx.recv() => {
-            colored_eprintln!("Lost connection to router");
+        _ = count_reached() => None,
+        _ = deadline() => {
+            // Without --count the timeout only bounds how long to listen
+            if let Some(limit) = &shared.limit {
+                colored_eprintln!(
+                    "Timed out after {:?}: received {} of {} events",
+                    subscribe_config.timeout.unwrap_or_default(),
+                    limit.received(),
+                    limit.count
+                );
+                outcome = Err(Failure::Timeout);
+            }
+            None
         }
-        _ = &mut join_handle => {
+        // The session already reported the lost connection
+        _ = disconnect_rx.recv() => None,
+        result = &mut join_handle => {
             // All sessions ended (e.g., all failed to connect)
             // Error messages already printed in run_session
+            Some(result)
         }
-    }
+    };
 
     // Signal remaining sessions to shutdown
     let _ = shutdown_tx.send(true);
     drop(disconnect_tx);
 
-    if !join_handle.is_finished() {
-        let _ = join_handle.await;
-    }
-
-    Ok(())
+    let result = match finished {
+        Some(result) => result,
+        None => join_handle.await,
+    };
+    // A task that panicked is a failure too, not a clean exit
+    outcome.and(result.unwrap_or(Err(Failure::Application)))
 }
@@ -1,5 +1,11 @@
 use crate::cli::Cli;
+use crate::filter::Filter;
+use crate::output::OutputFormat;
+use crate::profile::{Profile, ProfileFile};
+use crate::utils::{BytesFormat, Payload};
 use std::collections::HashMap;
+use std::fs;
+use std::time::Duration;
 use wampproto::authenticators::anonymous::AnonymousAuthenticator;
 use wampproto::authenticators::authenticator::ClientAuthenticator;
 use wampproto::authenticators::cryptosign::CryptoSignAuthenticator;
@@ -10,6 +16,10 @@ use xconn::async_::client::Client;
 use xconn::async_::session::Session;
 use xconn::sync::{CBORSerializerSpec, JSONSerializerSpec, MsgPackSerializerSpec, SerializerSpec};
 
+const DEFAULT_URL: &str = "ws://localhost:8080/ws";
+const DEFAULT_REALM: &str = "realm1";
+const DEFAULT_SERIALIZER: &str = "json";
+
 /// Global connection and authentication configuration.
 #[derive(Debug, Clone)]
 pub struct ConnectionConfig {
@@ -25,17 +35,77 @@ pub struct ConnectionConfig {
 
 impl ConnectionConfig {
     /// Connects to the router using the configured serializer and authentication method.
+    /// The transport is picked from the URL scheme; RawSocket connections negotiate
+    /// the handshake with the configured serializer.
     pub async fn connect(&self) -> Result<Session, Box<dyn std::error::Error>> {
+        let url = self.router_url()?;
         let serializer = self.create_serializer()?;
         let authenticator = self.create_authenticator()?;
 
         let client = Client::new(serializer, authenticator);
         client
-            .connect(&self.url, &self.realm)
+            .connect(&url, &self.realm)
             .await
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
     }
 
+    /// Checks the URL, serializer and credentials up front,
+    /// so mistakes are reported as usage errors before any connection is attempted.
+    fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.router_url()?;
+        self.create_serializer()?;
+        self.create_authenticator()?;
+        Ok(())
+    }
+
+    /// Validates the --url scheme and normalizes RawSocket aliases.
+    /// ws:// and wss:// use WebSocket, rs:// and rss:// use RawSocket over TCP (plain and TLS).
+    /// tcp:// and tcps:// are accepted as aliases for rs:// and rss://.
+    /// unix:///path/to/socket uses RawSocket over a Unix domain socket.
+    fn router_url(&self) -> Result<String, String> {
+        let Some((scheme, rest)) = self.url.split_once("://") else {
+            return Err(format!(
+                "Invalid URL '{}'. Expected <scheme>://<host>[:<port>]",
+                self.url
+            ));
+        };
+
+        let scheme = match scheme.to_lowercase().as_str() {
+            "ws" | "wss" => return Ok(self.url.clone()),
+            "rs" | "tcp" => "rs",
+            "rss" | "tcps" => "rss",
+            "unix" => {
+                if rest.is_empty() {
+                    return Err(format!(
+                        "Unix socket URL '{}' must include a path, e.g. unix:///run/router.sock",
+                        self.url
+                    ));
+                }
+                return Ok(format!("unix://{}", rest));
+            }
+            other => {
+                return Err(format!(
+                    "Unsupported URL scheme '{}'. Valid schemes: ws, wss, rs, rss, tcp, tcps, unix",
+                    other
+                ));
+            }
+        };
+
+        // RawSocket has no well-known port, so it must be given explicitly
+        let authority = rest.split('/').next().unwrap_or_default();
+        let has_port = authority
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+        if !has_port {
+            return Err(format!(
+                "RawSocket URL '{}' must include a port, e.g. {}://localhost:8081",
+                self.url, scheme
+            ));
+        }
+
+        Ok(format!("{}://{}", scheme, rest))
+    }
+
     /// Creates the appropriate serializer based on the --serializer option.
     fn create_serializer(&self) -> Result<Box<dyn SerializerSpec>, String> {
         match self.serializer.to_lowercase().as_str() {
@@ -50,7 +120,7 @@ impl ConnectionConfig {
     }
 
     /// Creates the appropriate authenticator based on authentication options.
-    /// Priority: --private-key > --secret > --ticket > anonymous
+    /// At most one of the credentials is set, see `Credentials::resolve`. Without one, anonymous is used.
     fn create_authenticator(
         &self,
     ) -> Result<Box<dyn ClientAuthenticator>, Box<dyn std::error::Error>> {
@@ -90,17 +160,198 @@ impl ConnectionConfig {
     }
 }
 
-impl From<&Cli> for ConnectionConfig {
-    fn from(cli: &Cli) -> Self {
+/// Builds the connection config from the command line, filling unset options
+/// from the --profile (if any) and then from the defaults.
+impl TryFrom<&Cli> for ConnectionConfig {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(cli: &Cli) -> Result<Self, Self::Error> {
+        let profile = match cli.profile {
+            Some(ref name) => {
+                let path = ProfileFile::path(cli.config.as_deref())?;
+                ProfileFile::load(&path)?.get(name)?.clone()
+            }
+            None => Profile::default(),
+        };
+
+        let credentials = Credentials::resolve(cli, &profile)?;
+        let config = Self {
+            url: cli
+                .url
+                .clone()
+                .or(profile.url)
+                .unwrap_or_else(|| DEFAULT_URL.to_string()),
+            realm: cli
+                .realm
+                .clone()
+                .or(profile.realm)
+                .unwrap_or_else(|| DEFAULT_REALM.to_string()),
+            authid: cli.authid.clone().or(profile.authid),
+            authrole: cli.authrole.clone().or(profile.authrole),
+            secret: credentials.secret,
+            private_key: credentials.private_key,
+            ticket: credentials.ticket,
+            serializer: cli
+                .serializer
+                .clone()
+                .or(profile.serializer)
+                .unwrap_or_else(|| DEFAULT_SERIALIZER.to_string()),
+        };
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// The credentials given at one level: the command line, the environment or the profile.
+#[derive(Default)]
+struct Credentials {
+    secret: Option<String>,
+    secret_file: Option<String>,
+    ask_secret: bool,
+    private_key: Option<String>,
+    private_key_file: Option<String>,
+    ticket: Option<String>,
+    ticket_file: Option<String>,
+}
+
+impl Credentials {
+    fn from_cli(cli: &Cli) -> Self {
         Self {
-            url: cli.url.clone(),
-            realm: cli.realm.clone(),
-            authid: cli.authid.clone(),
-            authrole: cli.authrole.clone(),
             secret: cli.secret.clone(),
+            secret_file: cli.secret_file.clone(),
+            ask_secret: cli.ask_secret,
             private_key: cli.private_key.clone(),
+            private_key_file: cli.private_key_file.clone(),
             ticket: cli.ticket.clone(),
-            serializer: cli.serializer.clone(),
+            ticket_file: cli.ticket_file.clone(),
+        }
+    }
+
+    fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Self {
+            secret: var("WICK_SECRET"),
+            secret_file: var("WICK_SECRET_FILE"),
+            ask_secret: false,
+            private_key: var("WICK_PRIVATE_KEY"),
+            private_key_file: var("WICK_PRIVATE_KEY_FILE"),
+            ticket: var("WICK_TICKET"),
+            ticket_file: var("WICK_TICKET_FILE"),
+        }
+    }
+
+    fn from_profile(profile: &Profile) -> Self {
+        Self {
+            secret: profile.secret.clone(),
+            secret_file: profile.secret_file.clone(),
+            ask_secret: profile.ask_secret,
+            private_key: profile.private_key.clone(),
+            private_key_file: profile.private_key_file.clone(),
+            ticket: profile.ticket.clone(),
+            ticket_file: profile.ticket_file.clone(),
+        }
+    }
+
+    /// The authentication methods with a credential at this level.
+    fn methods(&self) -> Vec<&'static str> {
+        let mut methods = Vec::new();
+        if self.private_key.is_some() || self.private_key_file.is_some() {
+            methods.push("cryptosign");
+        }
+        if self.secret.is_some() || self.secret_file.is_some() || self.ask_secret {
+            methods.push("wampcra");
+        }
+        if self.ticket.is_some() || self.ticket_file.is_some() {
+            methods.push("ticket");
+        }
+        methods
+    }
+
+    /// Picks the authentication method from the command line, else from the environment,
+    /// else from the profile, and only then reads its credential. A $WICK_PRIVATE_KEY or a
+    /// profile's private key therefore never overrides an explicit --ticket or --secret.
+    /// Setting more than one method at the same level is an error.
+    /// The returned credentials hold only the values, read from the files or the prompt.
+    fn resolve(cli: &Cli, profile: &Profile) -> Result<Self, String> {
+        let levels = [
+            ("command line", Self::from_cli(cli)),
+            ("environment", Self::from_env()),
+            ("profile", Self::from_profile(profile)),
+        ];
+        let Some((level, credentials)) = levels
+            .into_iter()
+            .find(|(_, credentials)| !credentials.methods().is_empty())
+        else {
+            return Ok(Self::default());
+        };
+
+        let methods = credentials.methods();
+        if methods.len() > 1 {
+            return Err(format!(
+                "Conflicting authentication methods in the {}: {}. Use only one",
+                level,
+                methods.join(", ")
+            ));
+        }
+
+        let secret = if credentials.ask_secret {
+            Some(prompt_secret()?)
+        } else {
+            resolve_credential(&credentials.secret, &credentials.secret_file)?
+        };
+        Ok(Self {
+            secret,
+            private_key: resolve_credential(
+                &credentials.private_key,
+                &credentials.private_key_file,
+            )?,
+            ticket: resolve_credential(&credentials.ticket, &credentials.ticket_file)?,
+            ..Self::default()
+        })
+    }
+}
+
+fn prompt_secret() -> Result<String, String> {
+    rpassword::prompt_password("Secret: ")
+        .map_err(|e| format!("Failed to read secret from terminal: {}", e))
+}
+
+/// Returns the credential given directly, or reads it from the file if one is given.
+fn resolve_credential(
+    value: &Option<String>,
+    file: &Option<String>,
+) -> Result<Option<String>, String> {
+    match (value, file) {
+        (Some(value), _) => Ok(Some(value.clone())),
+        (None, Some(path)) => read_credential_file(path).map(Some),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Reads a credential from a file, ignoring surrounding whitespace such as the trailing newline.
+fn read_credential_file(path: &str) -> Result<String, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    let value = content.trim();
+    if value.is_empty() {
+        return Err(format!("File '{}' is empty", path));
+    }
+    Ok(value.to_string())
+}
+
+/// Output options shared by the commands that print results, events and invocations.
+#[derive(Debug, Clone, Copy)]
+pub struct OutputConfig {
+    pub format: OutputFormat,
+    pub bytes_format: BytesFormat,
+}
+
+impl From<&Cli> for OutputConfig {
+    fn from(cli: &Cli) -> Self {
+        Self {
+            format: cli.output,
+            bytes_format: cli.bytes_format,
         }
     }
 }
@@ -109,31 +360,76 @@ impl From<&Cli> for ConnectionConfig {
 #[derive(Debug, Clone)]
 pub struct CallConfig {
     pub procedure: String,
-    pub args: Vec<String>,
-    pub kwargs: Vec<String>,
-    pub options: Vec<String>,
+    /// Arguments, keyword arguments and options are parsed once, ahead of the first message
+    pub args: Vec<Value>,
+    pub kwargs: Vec<(String, Value)>,
+    pub options: Vec<(String, Value)>,
+    pub payload: Option<Payload>,
+    pub bench: bool,
+    pub timeout: Option<Duration>,
     pub repeat: u32,
+    pub rate: Option<f64>,
+    pub ramp_up: Option<Duration>,
+    pub duration: Option<Duration>,
+    pub inflight: u32,
     pub parallel: u32,
     pub concurrency: usize,
+    pub output: OutputConfig,
 }
 
 /// Configuration specific to the Publish command.
 #[derive(Debug, Clone)]
 pub struct PublishConfig {
     pub topic: String,
-    pub args: Vec<String>,
-    pub kwargs: Vec<String>,
-    pub options: Vec<String>,
+    /// Arguments, keyword arguments and options are parsed once, ahead of the first message
+    pub args: Vec<Value>,
+    pub kwargs: Vec<(String, Value)>,
+    pub options: Vec<(String, Value)>,
+    pub payload: Option<Payload>,
     pub repeat: u32,
+    pub rate: Option<f64>,
+    pub ramp_up: Option<Duration>,
+    pub duration: Option<Duration>,
     pub parallel: u32,
     pub concurrency: usize,
     pub acknowledge: bool,
+    pub stdin: bool,
+    pub stdin_json: bool,
+    pub output: OutputConfig,
+}
+
+/// Configuration specific to the Register command.
+#[derive(Debug, Clone)]
+pub struct RegisterConfig {
+    pub procedure: String,
+    pub reconnect: bool,
+    pub max_reconnect_attempts: Option<u32>,
+    pub return_payload: Option<Payload>,
+    pub return_kwargs: Vec<(String, Value)>,
+    pub output: OutputConfig,
 }
 
 /// Configuration specific to the Subscribe command.
 #[derive(Debug, Clone)]
 pub struct SubscribeConfig {
-    pub topic: String,
+    pub topics: Vec<String>,
+    pub details: bool,
+    pub filter: Option<Filter>,
+    pub count: Option<u64>,
+    pub timeout: Option<Duration>,
     pub parallel: u32,
     pub concurrency: usize,
+    pub reconnect: bool,
+    pub max_reconnect_attempts: Option<u32>,
+    pub record: Option<String>,
+    pub output: OutputConfig,
+}
+
+/// Configuration specific to the Replay command.
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    pub file: String,
+    pub speed: f64,
+    pub acknowledge: bool,
+    pub output: OutputConfig,
 }
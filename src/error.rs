@@ -0,0 +1,129 @@
+use crate::colored_eprintln;
+use crate::config::OutputConfig;
+use crate::output::{OutputFormat, format_error};
+use std::fmt;
+use std::process::ExitCode;
+use tokio::task::JoinHandle;
+
+/// Why a command failed. Each kind maps to its own process exit code so that
+/// scripts can tell failures apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The router or a peer returned a WAMP error (exit code 1)
+    Application,
+    /// Invalid options or input, matching clap's own usage errors (exit code 2)
+    Usage,
+    /// Connecting failed or the connection to the router was lost (exit code 3)
+    Connection,
+    /// The router rejected the authentication (exit code 4)
+    Authentication,
+    /// A --timeout expired (exit code 5)
+    Timeout,
+    /// Interrupted with Ctrl+C while work was still in flight (exit code 130)
+    Interrupted,
+}
+
+/// ABORT reasons sent by the router when it rejects the credentials or the realm.
+const AUTHENTICATION_ABORTS: [&str; 4] = [
+    "wamp.error.authentication_failed",
+    "wamp.error.not_authorized",
+    "wamp.error.no_auth_method",
+    "wamp.error.authorization_failed",
+];
+
+impl Failure {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Failure::Application => 1,
+            Failure::Usage => 2,
+            Failure::Connection => 3,
+            Failure::Authentication => 4,
+            Failure::Timeout => 5,
+            Failure::Interrupted => 130,
+        }
+    }
+
+    /// Classifies an error returned while connecting to the router.
+    /// An ABORT with one of the AUTHENTICATION_ABORTS reasons is reported as Authentication.
+    pub fn from_connect_error(error: &dyn std::error::Error) -> Self {
+        let mut root = error;
+        while let Some(src) = root.source() {
+            root = src;
+        }
+
+        let message = root.to_string();
+        if AUTHENTICATION_ABORTS
+            .iter()
+            .any(|uri| message.contains(uri))
+        {
+            Failure::Authentication
+        } else {
+            Failure::Connection
+        }
+    }
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        ExitCode::from(failure.exit_code())
+    }
+}
+
+/// Waits for all sessions to finish and returns the first failure, if any.
+/// A session that panicked counts as an Application failure.
+pub async fn join_sessions(handles: Vec<JoinHandle<Result<(), Failure>>>) -> Result<(), Failure> {
+    let mut outcome = Ok(());
+    for handle in handles {
+        let result = handle.await.unwrap_or(Err(Failure::Application));
+        if let Err(failure) = result
+            && outcome.is_ok()
+        {
+            outcome = Err(failure);
+        }
+    }
+    outcome
+}
+
+/// A failure together with the message to print for it.
+#[derive(Debug)]
+pub struct CommandError {
+    pub failure: Failure,
+    pub message: String,
+    /// The message is a WAMP error in a machine-readable --output format
+    structured: bool,
+}
+
+impl CommandError {
+    pub fn new(failure: Failure, message: impl Into<String>) -> Self {
+        Self {
+            failure,
+            message: message.into(),
+            structured: false,
+        }
+    }
+
+    /// A WAMP error returned by the router, rendered in the active output format.
+    pub fn wamp(error: &xconn::sync::Error, output_config: OutputConfig) -> Self {
+        Self {
+            failure: Failure::Application,
+            message: format_error(error, output_config),
+            structured: !matches!(output_config.format, OutputFormat::Pretty),
+        }
+    }
+
+    /// Prints the message to stderr after `label`. Structured output is printed
+    /// as is, without the label or the colored [ERROR] prefix.
+    pub fn print(&self, label: &str) {
+        if self.structured {
+            eprintln!("{}", self.message);
+        } else {
+            colored_eprintln!("{}{}", label, self.message);
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
@@ -0,0 +1,457 @@
+use serde_json::Value as SerdeValue;
+use std::cmp::Ordering;
+
+/// A --filter predicate such as `.kwargs.level == "error"`, evaluated against
+/// {"args": [...], "kwargs": {...}, "details": {...}}.
+///
+/// Supports paths (`.kwargs.level`, `.args[0]`, `.kwargs["some key"]`), string, number,
+/// boolean and null literals, the comparisons == != < <= > >=, `and`, `or`, `not` and
+/// parentheses. A path on its own is true unless it is missing, null or false.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Path(Vec<Segment>),
+    Literal(SerdeValue),
+    Compare(Box<Expr>, Op, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parser = Parser { input, pos: 0 };
+        let expr = parser.or()?;
+
+        parser.skip_whitespace();
+        if parser.pos < input.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(Self { expr })
+    }
+
+    pub fn matches(&self, value: &SerdeValue) -> bool {
+        truthy(&self.expr.eval(value))
+    }
+}
+
+impl Expr {
+    fn eval(&self, value: &SerdeValue) -> SerdeValue {
+        match self {
+            Expr::Path(segments) => segments
+                .iter()
+                .try_fold(value, |current, segment| match segment {
+                    Segment::Key(key) => current.get(key),
+                    Segment::Index(index) => current.get(index),
+                })
+                .cloned()
+                .unwrap_or(SerdeValue::Null),
+            Expr::Literal(literal) => literal.clone(),
+            Expr::Compare(left, op, right) => {
+                SerdeValue::Bool(compare(&left.eval(value), *op, &right.eval(value)))
+            }
+            Expr::And(left, right) => {
+                SerdeValue::Bool(truthy(&left.eval(value)) && truthy(&right.eval(value)))
+            }
+            Expr::Or(left, right) => {
+                SerdeValue::Bool(truthy(&left.eval(value)) || truthy(&right.eval(value)))
+            }
+            Expr::Not(inner) => SerdeValue::Bool(!truthy(&inner.eval(value))),
+        }
+    }
+}
+
+fn truthy(value: &SerdeValue) -> bool {
+    !matches!(value, SerdeValue::Null | SerdeValue::Bool(false))
+}
+
+/// Numbers compare by value, so 1 == 1.0. Only numbers and strings are ordered;
+/// ordering anything else is false.
+fn compare(left: &SerdeValue, op: Op, right: &SerdeValue) -> bool {
+    let ordering = match (left, right) {
+        (SerdeValue::Number(l), SerdeValue::Number(r)) => l
+            .as_f64()
+            .zip(r.as_f64())
+            .and_then(|(l, r)| l.partial_cmp(&r)),
+        (SerdeValue::String(l), SerdeValue::String(r)) => Some(l.cmp(r)),
+        _ => None,
+    };
+
+    match op {
+        Op::Eq => ordering.map_or(left == right, Ordering::is_eq),
+        Op::Ne => !ordering.map_or(left == right, Ordering::is_eq),
+        Op::Lt => ordering.is_some_and(Ordering::is_lt),
+        Op::Le => ordering.is_some_and(Ordering::is_le),
+        Op::Gt => ordering.is_some_and(Ordering::is_gt),
+        Op::Ge => ordering.is_some_and(Ordering::is_ge),
+    }
+}
+
+/// Recursive descent parser, lowest precedence first: or, and, not, comparison.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.eat_keyword("or") {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while self.eat_keyword("and") {
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.operand()?;
+
+        // Two-character operators first, so "<=" is not read as "<"
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        for (token, op) in ops {
+            if self.eat(token) {
+                let right = self.operand()?;
+                return Ok(Expr::Compare(Box::new(left), op, Box::new(right)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.or()?;
+                if !self.eat(")") {
+                    return Err(self.error("expected ')'"));
+                }
+                Ok(expr)
+            }
+            Some('.') => {
+                self.pos += 1;
+                self.path()
+            }
+            Some('"') => Ok(Expr::Literal(SerdeValue::String(self.string()?))),
+            Some(c) if c.is_ascii_digit() || c == '-' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => match self.identifier() {
+                "true" => Ok(Expr::Literal(SerdeValue::Bool(true))),
+                "false" => Ok(Expr::Literal(SerdeValue::Bool(false))),
+                "null" => Ok(Expr::Literal(SerdeValue::Null)),
+                other => Err(format!(
+                    "Invalid filter '{}': unknown word '{}', paths start with '.'",
+                    self.input, other
+                )),
+            },
+            _ => Err(self.error("expected a path, a value or '('")),
+        }
+    }
+
+    /// Parses the segments of a path after its leading '.'.
+    fn path(&mut self) -> Result<Expr, String> {
+        let mut segments = Vec::new();
+
+        if self.peek().is_some_and(is_identifier_char) {
+            segments.push(Segment::Key(self.identifier().to_string()));
+        }
+
+        loop {
+            match self.peek() {
+                Some('.') => {
+                    self.pos += 1;
+                    if !self.peek().is_some_and(is_identifier_char) {
+                        return Err(self.error("expected a key after '.'"));
+                    }
+                    segments.push(Segment::Key(self.identifier().to_string()));
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    let segment = if self.peek() == Some('"') {
+                        Segment::Key(self.string()?)
+                    } else {
+                        let digits = self.take_while(|c| c.is_ascii_digit());
+                        Segment::Index(
+                            digits
+                                .parse()
+                                .map_err(|_| self.error("expected an index or a quoted key"))?,
+                        )
+                    };
+                    if !self.eat("]") {
+                        return Err(self.error("expected ']'"));
+                    }
+                    segments.push(segment);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(Expr::Path(segments))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        let mut escaped = false;
+        for (offset, c) in self.input[start + 1..].char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    let end = start + 1 + offset + 1;
+                    self.pos = end;
+                    return serde_json::from_str(&self.input[start..end])
+                        .map_err(|e| format!("Invalid filter '{}': {}", self.input, e));
+                }
+                _ => {}
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn number(&mut self) -> Result<Expr, String> {
+        let start = self.pos;
+        let number =
+            self.take_while(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'));
+        serde_json::from_str::<serde_json::Number>(number)
+            .map(|n| Expr::Literal(SerdeValue::Number(n)))
+            .map_err(|_| {
+                format!(
+                    "Invalid filter '{}': invalid number at position {}",
+                    self.input, start
+                )
+            })
+    }
+
+    fn identifier(&mut self) -> &'a str {
+        self.take_while(is_identifier_char)
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        let len = self.input[start..]
+            .find(|c: char| !predicate(c))
+            .unwrap_or(self.input.len() - start);
+        self.pos += len;
+        let input = self.input;
+        &input[start..self.pos]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    /// Consumes `token` after optional whitespace if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.input[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Like `eat`, but only matches whole words, so "order" is not read as "or".
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let rest = &self.input[self.pos..];
+        let whole_word = rest
+            .strip_prefix(keyword)
+            .is_some_and(|after| !after.starts_with(is_identifier_char));
+        if whole_word {
+            self.pos += keyword.len();
+        }
+        whole_word
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!(
+            "Invalid filter '{}': {} at position {}",
+            self.input, message, self.pos
+        )
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event() -> SerdeValue {
+        json!({
+            "args": [{"x": 3, "tags": ["a", "b"]}, "second"],
+            "kwargs": {"level": "error", "count": 10, "ratio": 0.5, "ok": false, "none": null, "some key": 1},
+            "details": {"topic": "app.logs", "publication": 42},
+        })
+    }
+
+    fn matches(filter: &str) -> bool {
+        Filter::parse(filter)
+            .unwrap_or_else(|e| panic!("{}", e))
+            .matches(&event())
+    }
+
+    #[test]
+    fn string_comparisons() {
+        assert!(matches(r#".kwargs.level == "error""#));
+        assert!(!matches(r#".kwargs.level != "error""#));
+        assert!(matches(r#".kwargs.level < "warning""#));
+        assert!(matches(r#".details.topic >= "app""#));
+        assert!(matches(r#""a\"b" == "a\"b""#));
+    }
+
+    #[test]
+    fn number_comparisons() {
+        assert!(matches(".kwargs.count == 10"));
+        assert!(matches(".kwargs.count == 10.0"));
+        assert!(matches(".kwargs.count > 9"));
+        assert!(matches(".kwargs.count <= 10"));
+        assert!(matches(".kwargs.ratio < 1e0"));
+        assert!(matches(".kwargs.ratio >= -1"));
+        assert!(!matches(".kwargs.count < 10"));
+    }
+
+    #[test]
+    fn ordering_different_types_is_false() {
+        assert!(!matches(r#".kwargs.count > "1""#));
+        assert!(!matches(r#".kwargs.count < "1""#));
+        assert!(!matches(r#".kwargs.count == "10""#));
+        assert!(matches(r#".kwargs.count != "10""#));
+    }
+
+    #[test]
+    fn and_or_not() {
+        assert!(matches(r#".kwargs.level == "error" and .kwargs.count > 5"#));
+        assert!(!matches(
+            r#".kwargs.level == "error" and .kwargs.count > 50"#
+        ));
+        assert!(matches(r#".kwargs.level == "info" or .kwargs.count > 5"#));
+        assert!(!matches(r#".kwargs.level == "info" or .kwargs.count > 50"#));
+        assert!(matches(".kwargs.ok == false and not .kwargs.ok"));
+        assert!(matches("not not .kwargs.count"));
+    }
+
+    #[test]
+    fn precedence() {
+        // and binds tighter than or
+        assert!(matches("true or false and false"));
+        assert!(!matches("(true or false) and false"));
+        // not binds tighter than and, and applies to the whole comparison
+        assert!(!matches("not true and true"));
+        assert!(matches("not .kwargs.count == 1 and true"));
+        assert!(matches("not (.kwargs.count == 1 and false)"));
+    }
+
+    #[test]
+    fn paths() {
+        assert!(matches(".args[0].x == 3"));
+        assert!(matches(r#".args[0].tags[1] == "b""#));
+        assert!(matches(r#".args[1] == "second""#));
+        assert!(matches(r#".kwargs["some key"] == 1"#));
+        assert!(matches(r#".details.topic == "app.logs""#));
+        assert!(matches(".kwargs"));
+    }
+
+    #[test]
+    fn missing_paths_are_null() {
+        assert!(!matches(".kwargs.missing"));
+        assert!(!matches(".args[5]"));
+        assert!(!matches(".args[0].x.y"));
+        assert!(!matches(".kwargs.level.nested"));
+        assert!(matches(".kwargs.missing == null"));
+        assert!(matches(".kwargs.none == null"));
+        assert!(!matches(".kwargs.none"));
+        assert!(!matches(".kwargs.missing > 1"));
+        assert!(matches("not .kwargs.missing"));
+    }
+
+    #[test]
+    fn keywords_need_whole_words() {
+        let event = json!({"kwargs": {"order": 1, "android": true}});
+        let filter = Filter::parse(".kwargs.order == 1 and .kwargs.android").unwrap();
+        assert!(filter.matches(&event));
+    }
+
+    #[test]
+    fn parse_errors() {
+        for input in [
+            "",
+            ".kwargs.level ==",
+            r#".kwargs.level == "error"#,
+            "(.kwargs.count > 1",
+            ".kwargs.",
+            ".args[x]",
+            ".args[0",
+            "level == 1",
+            ".kwargs.count > 1 )",
+            ".kwargs.count === 1",
+            "1.2.3",
+        ] {
+            assert!(
+                Filter::parse(input).is_err(),
+                "'{}' should not parse",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn parse_errors_name_the_filter() {
+        let error = Filter::parse("level == 1").unwrap_err();
+        assert!(
+            error.starts_with("Invalid filter 'level == 1'"),
+            "{}",
+            error
+        );
+        assert!(error.contains("paths start with '.'"), "{}", error);
+    }
+}
@@ -0,0 +1,42 @@
+use tokio::signal;
+use tokio::sync::watch;
+
+/// Process-wide Ctrl+C handling. One task listens for the signal and every session
+/// can check or wait on it, so sessions that are still connecting or not yet started
+/// stop as well. A second Ctrl+C exits right away with code 130.
+#[derive(Clone)]
+pub struct Interrupt {
+    receiver: watch::Receiver<bool>,
+}
+
+impl Interrupt {
+    pub fn listen() -> Self {
+        let (sender, receiver) = watch::channel(false);
+
+        tokio::spawn(async move {
+            if signal::ctrl_c().await.is_err() {
+                // No signal handler, keep the sender so waiters never see a closed channel
+                std::future::pending::<()>().await;
+            }
+            let _ = sender.send(true);
+
+            // The sessions may hang while leaving; don't make the user wait for them
+            if signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        });
+
+        Self { receiver }
+    }
+
+    /// Whether Ctrl+C has been pressed.
+    pub fn is_set(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Waits until Ctrl+C is pressed, returning at once if it already was.
+    pub async fn wait(&self) {
+        let mut receiver = self.receiver.clone();
+        let _ = receiver.wait_for(|interrupted| *interrupted).await;
+    }
+}
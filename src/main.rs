@@ -1,81 +1,204 @@
+mod bench;
 mod cli;
 mod commands;
 mod config;
+mod error;
+mod filter;
+mod interrupt;
+mod output;
+mod pacer;
+mod profile;
+mod reconnect;
+mod record;
 mod utils;
 
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
 use cli::{Cli, Commands};
-use config::{CallConfig, ConnectionConfig, PublishConfig};
+use config::{
+    CallConfig, ConnectionConfig, OutputConfig, PublishConfig, RegisterConfig, ReplayConfig,
+    SubscribeConfig,
+};
+use error::Failure;
+use std::fmt::Display;
+use std::process::ExitCode;
+use utils::{Payload, load_topics, parse_key_values, parse_values};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+async fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let cli =
+        Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.format(&mut Cli::command()).exit());
+    match run(cli, &matches).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => failure.into(),
+    }
+}
 
+/// Prints an error found while setting up a command and reports it as a usage error.
+fn usage_error(error: impl Display) -> Failure {
+    colored_eprintln!("{}", error);
+    Failure::Usage
+}
+
+async fn run(cli: Cli, matches: &ArgMatches) -> Result<(), Failure> {
     if let Commands::Keygen { output_file } = cli.command {
-        return commands::keygen::handle(output_file);
+        return commands::keygen::handle(output_file).map_err(usage_error);
     }
 
-    let conn_config = ConnectionConfig::from(&cli);
+    if let Commands::Profile { ref command } = cli.command {
+        return commands::profile::handle(&cli, matches, command).map_err(usage_error);
+    }
+
+    let conn_config = ConnectionConfig::try_from(&cli).map_err(usage_error)?;
+    let output = OutputConfig::from(&cli);
 
     match cli.command {
         Commands::Call {
             procedure,
             args,
+            payload,
             kwargs,
             options,
+            json_args,
+            bench,
+            timeout,
             repeat,
+            rate,
+            ramp_up,
+            duration,
+            inflight,
             parallel,
             concurrency,
         } => {
             let call_config = CallConfig {
                 procedure,
-                args,
-                kwargs,
-                options,
+                args: parse_values(&args, json_args).map_err(usage_error)?,
+                kwargs: parse_key_values(&kwargs, json_args).map_err(usage_error)?,
+                options: parse_key_values(&options, false).map_err(usage_error)?,
+                payload: payload
+                    .as_deref()
+                    .map(Payload::load)
+                    .transpose()
+                    .map_err(usage_error)?,
+                bench,
+                timeout,
                 repeat,
+                rate,
+                ramp_up,
+                duration,
+                inflight,
                 parallel,
                 concurrency,
+                output,
             };
             commands::call::handle(conn_config, call_config).await?;
         }
-        Commands::Register { procedure } => {
-            commands::register::handle(conn_config, &procedure).await?;
+        Commands::Register {
+            procedure,
+            reconnect,
+            max_reconnect_attempts,
+            return_payload,
+            return_kwargs,
+        } => {
+            let register_config = RegisterConfig {
+                procedure,
+                reconnect,
+                max_reconnect_attempts,
+                return_payload: return_payload
+                    .as_deref()
+                    .map(Payload::parse)
+                    .transpose()
+                    .map_err(usage_error)?,
+                return_kwargs: parse_key_values(&return_kwargs, false).map_err(usage_error)?,
+                output,
+            };
+            commands::register::handle(conn_config, register_config).await?;
         }
         Commands::Subscribe {
-            topic,
+            mut topics,
+            topics_file,
+            details,
+            filter,
+            count,
+            timeout,
             parallel,
             concurrency,
+            reconnect,
+            max_reconnect_attempts,
+            record,
         } => {
-            let subscribe_config = config::SubscribeConfig {
-                topic,
+            if let Some(path) = topics_file {
+                topics.extend(load_topics(&path).map_err(usage_error)?);
+            }
+            let subscribe_config = SubscribeConfig {
+                topics,
+                details,
+                filter,
+                count,
+                timeout,
                 parallel,
                 concurrency,
+                reconnect,
+                max_reconnect_attempts,
+                record,
+                output,
             };
             commands::subscribe::handle(conn_config, subscribe_config).await?;
         }
         Commands::Publish {
             topic,
             args,
+            payload,
             kwargs,
             options,
+            json_args,
             repeat,
+            rate,
+            ramp_up,
+            duration,
             parallel,
             concurrency,
             acknowledge,
+            stdin,
+            stdin_json,
         } => {
             let publish_config = PublishConfig {
                 topic,
-                args,
-                kwargs,
-                options,
+                args: parse_values(&args, json_args).map_err(usage_error)?,
+                kwargs: parse_key_values(&kwargs, json_args).map_err(usage_error)?,
+                options: parse_key_values(&options, false).map_err(usage_error)?,
+                payload: payload
+                    .as_deref()
+                    .map(Payload::load)
+                    .transpose()
+                    .map_err(usage_error)?,
                 repeat,
+                rate,
+                ramp_up,
+                duration,
                 parallel,
                 concurrency,
                 acknowledge,
+                stdin: stdin || stdin_json,
+                stdin_json,
+                output,
             };
             commands::publish::handle(conn_config, publish_config).await?;
         }
-        Commands::Keygen { .. } => unreachable!(), // Handled above
+        Commands::Replay {
+            file,
+            speed,
+            acknowledge,
+        } => {
+            let replay_config = ReplayConfig {
+                file,
+                speed,
+                acknowledge,
+                output,
+            };
+            commands::replay::handle(conn_config, replay_config).await?;
+        }
+        Commands::Keygen { .. } | Commands::Profile { .. } => unreachable!(), // Handled above
     }
 
     Ok(())
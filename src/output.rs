@@ -0,0 +1,111 @@
+use crate::colored_eprintln;
+use crate::config::OutputConfig;
+use crate::utils::ErrorOutput;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value as SerdeValue;
+
+/// How results, events and invocations are printed.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Pretty-printed JSON
+    #[default]
+    Pretty,
+    /// Compact JSON, one object per line (alias: ndjson)
+    #[value(alias = "ndjson")]
+    Json,
+    /// YAML documents separated by '---'
+    Yaml,
+    /// Two-column table of args and kwargs
+    Table,
+    /// The first positional argument only, strings unquoted
+    Raw,
+}
+
+/// Renders a value in the given format, without a trailing newline.
+pub fn format_output<T: Serialize>(value: &T, format: OutputFormat) -> Result<String, String> {
+    let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+
+    match format {
+        OutputFormat::Pretty => serde_json::to_string_pretty(&value).map_err(|e| e.to_string()),
+        OutputFormat::Json => serde_json::to_string(&value).map_err(|e| e.to_string()),
+        OutputFormat::Yaml => serde_norway::to_string(&value)
+            .map(|yaml| format!("---\n{}", yaml.trim_end()))
+            .map_err(|e| e.to_string()),
+        OutputFormat::Table => Ok(format_table(&value)),
+        OutputFormat::Raw => Ok(format_raw(&value)),
+    }
+}
+
+/// Renders a WAMP error with its args, kwargs and details in the active format.
+/// Raw prints the URI followed by the first argument, e.g. "app.error.invalid: name is required".
+pub fn format_error(error: &xconn::sync::Error, output_config: OutputConfig) -> String {
+    let output = ErrorOutput::new(error, output_config.bytes_format);
+
+    match output_config.format {
+        OutputFormat::Raw => match format_raw(&serde_json::json!(output)) {
+            detail if detail.is_empty() => output.uri,
+            detail => format!("{}: {}", output.uri, detail),
+        },
+        format => format_output(&output, format).unwrap_or(output.uri),
+    }
+}
+
+/// Prints a WAMP error to stderr. Only the pretty format gets the colored [ERROR] prefix,
+/// so the other formats stay machine-readable.
+pub fn print_error(error: &xconn::sync::Error, output_config: OutputConfig) {
+    let text = format_error(error, output_config);
+    match output_config.format {
+        OutputFormat::Pretty => colored_eprintln!("{}", text),
+        _ => eprintln!("{}", text),
+    }
+}
+
+/// Formats a scalar unquoted; lists and dicts as compact JSON.
+fn format_scalar(value: &SerdeValue) -> String {
+    match value {
+        SerdeValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Prints one row per top-level field, expanding args into args[N] and kwargs into kwargs.KEY.
+fn format_table(value: &SerdeValue) -> String {
+    let mut rows = Vec::new();
+
+    if let SerdeValue::Object(fields) = value {
+        for (name, field) in fields {
+            match field {
+                SerdeValue::Array(items) => {
+                    for (i, item) in items.iter().enumerate() {
+                        rows.push((format!("{}[{}]", name, i), format_scalar(item)));
+                    }
+                }
+                SerdeValue::Object(entries) => {
+                    for (key, entry) in entries {
+                        rows.push((format!("{}.{}", name, key), format_scalar(entry)));
+                    }
+                }
+                other => rows.push((name.clone(), format_scalar(other))),
+            }
+        }
+    }
+
+    let width = rows.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(key, value)| format!("{:<width$}  {}", key, value, width = width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Prints args[0] for use in shell scripts, falling back to kwargs when there are no args.
+fn format_raw(value: &SerdeValue) -> String {
+    if let Some(first) = value.get("args").and_then(|args| args.get(0)) {
+        return format_scalar(first);
+    }
+
+    match value.get("kwargs") {
+        Some(SerdeValue::Object(kwargs)) if !kwargs.is_empty() => format_scalar(&value["kwargs"]),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,144 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Decides when each call or publish of a session is sent, from --repeat, --rate,
+/// --ramp-up and --duration.
+pub struct Pacer {
+    repeat: u32,
+    rate: Option<f64>,
+    ramp_up: Duration,
+    duration: Option<Duration>,
+    started: Instant,
+    sent: u32,
+}
+
+impl Pacer {
+    /// `rate` is in messages per second. With `duration` set, `repeat` is ignored and
+    /// messages are sent until the duration has elapsed.
+    pub fn new(
+        repeat: u32,
+        rate: Option<f64>,
+        ramp_up: Option<Duration>,
+        duration: Option<Duration>,
+    ) -> Self {
+        Self {
+            repeat,
+            rate,
+            ramp_up: ramp_up.unwrap_or_default(),
+            duration,
+            started: Instant::now(),
+            sent: 0,
+        }
+    }
+
+    /// Waits until the next message is due and returns its iteration number (starting at 1),
+    /// or None once the session is done.
+    pub async fn next(&mut self) -> Option<u32> {
+        let due = self.rate.map(|rate| self.started + self.offset(rate));
+
+        match self.duration {
+            Some(duration) => {
+                let end = self.started + duration;
+                if due.unwrap_or_else(Instant::now) >= end {
+                    return None;
+                }
+            }
+            None if self.sent >= self.repeat => return None,
+            None => {}
+        }
+
+        if let Some(due) = due {
+            tokio::time::sleep_until(due).await;
+        }
+
+        self.sent += 1;
+        Some(self.sent)
+    }
+
+    /// Time from the start at which the next message is due. During the ramp-up the
+    /// rate grows linearly from zero, so the first messages are spread out further.
+    fn offset(&self, rate: f64) -> Duration {
+        let sent = self.sent as f64;
+        let ramp_up = self.ramp_up.as_secs_f64();
+        let ramp_up_messages = rate * ramp_up / 2.0;
+
+        let seconds = if sent < ramp_up_messages {
+            (2.0 * ramp_up * sent / rate).sqrt()
+        } else {
+            ramp_up + (sent - ramp_up_messages) / rate
+        };
+        Duration::from_secs_f64(seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offset_after(pacer: &mut Pacer, sent: u32, rate: f64) -> Duration {
+        pacer.sent = sent;
+        pacer.offset(rate)
+    }
+
+    /// The ramp-up uses a square root, so compare to the microsecond.
+    fn assert_secs(actual: Duration, expected: f64) {
+        assert!(
+            (actual.as_secs_f64() - expected).abs() < 1e-6,
+            "{:?} != {}s",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn first_message_is_due_at_once() {
+        let mut pacer = Pacer::new(10, Some(10.0), None, None);
+        assert_eq!(offset_after(&mut pacer, 0, 10.0), Duration::ZERO);
+
+        let mut pacer = Pacer::new(10, Some(10.0), Some(Duration::from_secs(5)), None);
+        assert_eq!(offset_after(&mut pacer, 0, 10.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn steady_rate_spaces_messages_evenly() {
+        let mut pacer = Pacer::new(100, Some(10.0), None, None);
+        assert_eq!(
+            offset_after(&mut pacer, 1, 10.0),
+            Duration::from_millis(100)
+        );
+        assert_eq!(offset_after(&mut pacer, 10, 10.0), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn ramp_up_reaches_the_rate_at_its_end() {
+        // 10/s over a 10s ramp sends 50 messages during the ramp, then one every 100ms
+        let mut pacer = Pacer::new(100, Some(10.0), Some(Duration::from_secs(10)), None);
+        assert_secs(offset_after(&mut pacer, 5, 10.0), 10f64.sqrt());
+        assert_secs(offset_after(&mut pacer, 50, 10.0), 10.0);
+        assert_secs(offset_after(&mut pacer, 60, 10.0), 11.0);
+    }
+
+    #[test]
+    fn ramp_longer_than_the_duration_sends_fewer_messages() {
+        // A 5m ramp cut short by a 5s duration only gets to the start of the ramp
+        let rate = 10.0;
+        let duration = Duration::from_secs(5);
+        let mut pacer = Pacer::new(
+            1,
+            Some(rate),
+            Some(Duration::from_secs(300)),
+            Some(duration),
+        );
+
+        let sent = (0..)
+            .take_while(|&n| offset_after(&mut pacer, n, rate) < duration)
+            .count();
+        assert_eq!(sent, 1);
+
+        let mut pacer = Pacer::new(1, Some(rate), Some(Duration::from_secs(10)), Some(duration));
+        let sent = (0..)
+            .take_while(|&n| offset_after(&mut pacer, n, rate) < duration)
+            .count();
+        assert_eq!(sent, 13);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::cli::Cli;
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Connection options stored under a named profile.
+/// Every field is optional; unset fields fall back to the CLI defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub realm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authrole: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_file: Option<String>,
+    /// Prompt for the secret when the profile is used
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ask_secret: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serializer: Option<String>,
+}
+
+impl Profile {
+    /// Returns a copy with secrets replaced by a placeholder, for display.
+    pub fn masked(&self) -> Self {
+        const MASK: &str = "********";
+        let mask = |v: &Option<String>| v.as_ref().map(|_| MASK.to_string());
+        Self {
+            secret: mask(&self.secret),
+            private_key: mask(&self.private_key),
+            ticket: mask(&self.ticket),
+            ..self.clone()
+        }
+    }
+
+    /// Builds a profile from the connection options given on the command line.
+    /// Values clap filled in from WICK_* variables are left out, so the environment
+    /// is never saved.
+    pub fn from_command_line(cli: &Cli, matches: &ArgMatches) -> Self {
+        let given = |id: &str, value: &Option<String>| {
+            value
+                .clone()
+                .filter(|_| matches.value_source(id) == Some(ValueSource::CommandLine))
+        };
+
+        Self {
+            url: given("url", &cli.url),
+            realm: given("realm", &cli.realm),
+            authid: given("authid", &cli.authid),
+            authrole: given("authrole", &cli.authrole),
+            secret: cli.secret.clone(),
+            secret_file: cli.secret_file.clone(),
+            ask_secret: cli.ask_secret,
+            private_key: cli.private_key.clone(),
+            private_key_file: cli.private_key_file.clone(),
+            ticket: cli.ticket.clone(),
+            ticket_file: cli.ticket_file.clone(),
+            serializer: given("serializer", &cli.serializer),
+        }
+    }
+}
+
+/// The wick config file, holding profiles under `[profiles.<name>]` tables.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ProfileFile {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl ProfileFile {
+    /// Resolves the config file path: --config if given,
+    /// otherwise $XDG_CONFIG_HOME/wick/config.toml or ~/.config/wick/config.toml.
+    pub fn path(config: Option<&str>) -> Result<PathBuf, String> {
+        if let Some(path) = config {
+            return Ok(PathBuf::from(path));
+        }
+
+        let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".config"))
+                .ok_or("Cannot locate the config directory, use --config to set the path")?,
+        };
+        Ok(base.join("wick").join("config.toml"))
+    }
+
+    /// Loads the config file. A missing file is treated as empty.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(format!("Failed to read config '{}': {}", path.display(), e).into());
+            }
+        };
+
+        toml::from_str(&content)
+            .map_err(|e| format!("Invalid config '{}': {}", path.display(), e).into())
+    }
+
+    /// Writes the config file, creating its parent directory if needed.
+    /// On unix the file is only readable by its owner, as it may hold secrets.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let content = toml::to_string_pretty(self)?;
+        write_private(path, content.as_bytes())
+            .map_err(|e| format!("Failed to write config '{}': {}", path.display(), e).into())
+    }
+
+    /// Looks up a profile by name.
+    pub fn get(&self, name: &str) -> Result<&Profile, String> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| format!("Profile '{}' not found", name))
+    }
+}
+
+/// Writes a file with mode 0o600 on unix, also tightening the mode of an existing file.
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(content)
+}
@@ -0,0 +1,172 @@
+use crate::error::{CommandError, Failure};
+use crate::{colored_eprintln, colored_println};
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+use xconn::async_::session::Session;
+
+/// Exponential backoff with jitter between reconnect attempts.
+pub struct Backoff {
+    attempt: u32,
+    max_attempts: Option<u32>,
+}
+
+impl Backoff {
+    const INITIAL_DELAY: Duration = Duration::from_millis(500);
+    const MAX_DELAY: Duration = Duration::from_secs(30);
+
+    pub fn new(max_attempts: Option<u32>) -> Self {
+        Self {
+            attempt: 0,
+            max_attempts,
+        }
+    }
+
+    /// Number of attempts made so far.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Returns the delay before the next attempt, or None once max attempts are exhausted.
+    /// The delay doubles on every attempt up to MAX_DELAY and is jittered down by up to half.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max| self.attempt >= max) {
+            return None;
+        }
+
+        let base = Self::INITIAL_DELAY
+            .saturating_mul(1 << self.attempt.min(16))
+            .min(Self::MAX_DELAY);
+        self.attempt += 1;
+
+        let jitter = rand::thread_rng().gen_range(0.5..=1.0);
+        Some(base.mul_f64(jitter))
+    }
+}
+
+/// Retries `connect` with exponential backoff until it succeeds or the attempts run out.
+/// Only connection failures are retried; an authentication or application error, such as a
+/// rejected registration, would fail the same way on every attempt and ends the retries.
+/// `label` prefixes every log line, e.g. "Session 2: " when running parallel sessions.
+async fn reconnect_with_backoff<F, Fut>(
+    max_attempts: Option<u32>,
+    label: &str,
+    mut connect: F,
+) -> Result<Session, Failure>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Session, CommandError>>,
+{
+    let mut backoff = Backoff::new(max_attempts);
+
+    while let Some(delay) = backoff.next_delay() {
+        colored_println!(
+            "{}Reconnecting in {:.1}s (attempt {})",
+            label,
+            delay.as_secs_f64(),
+            backoff.attempt()
+        );
+        tokio::time::sleep(delay).await;
+
+        match connect().await {
+            Ok(session) => {
+                colored_println!("{}Reconnected to router", label);
+                return Ok(session);
+            }
+            Err(e) if e.failure == Failure::Connection => {
+                colored_eprintln!("{}Reconnect attempt failed: {}", label, e)
+            }
+            Err(e) => {
+                e.print(label);
+                return Err(e.failure);
+            }
+        }
+    }
+
+    colored_eprintln!(
+        "{}Giving up after {} reconnect attempts",
+        label,
+        backoff.attempt()
+    );
+    Err(Failure::Connection)
+}
+
+/// How a session kept up by `keep_connected` ended.
+pub enum SessionEnd {
+    /// `stop` completed, and the session was left if it was up
+    Stopped,
+    /// The session never came up
+    NotConnected(Failure),
+    /// The connection was lost and could not be re-established
+    Lost(Failure),
+}
+
+/// Keeps a session up until `stop` completes. With `reconnect`, a router that can't be
+/// reached at startup and a connection that drops later are both retried with backoff;
+/// without it the first failure ends the session. `connected` runs once the session is
+/// first up.
+pub async fn keep_connected<F, Fut, S, StopFut>(
+    reconnect: bool,
+    max_attempts: Option<u32>,
+    label: &str,
+    mut connect: F,
+    stop: S,
+    connected: impl FnOnce(),
+) -> SessionEnd
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Session, CommandError>>,
+    S: Fn() -> StopFut,
+    StopFut: Future<Output = ()>,
+{
+    let first = tokio::select! {
+        result = connect() => result,
+        _ = stop() => return SessionEnd::Stopped,
+    };
+
+    let mut session = match first {
+        Ok(session) => session,
+        Err(e) if e.failure == Failure::Connection && reconnect => {
+            e.print(label);
+            tokio::select! {
+                result = reconnect_with_backoff(max_attempts, label, &mut connect) => match result {
+                    Ok(session) => session,
+                    Err(failure) => return SessionEnd::NotConnected(failure),
+                },
+                _ = stop() => return SessionEnd::Stopped,
+            }
+        }
+        Err(e) => {
+            e.print(label);
+            return SessionEnd::NotConnected(e.failure);
+        }
+    };
+
+    connected();
+
+    loop {
+        tokio::select! {
+            _ = stop() => {
+                if let Err(e) = session.leave().await {
+                    colored_eprintln!("{}Error leaving: {}", label, e);
+                }
+                return SessionEnd::Stopped;
+            }
+            _ = session.wait_disconnect() => {
+                colored_eprintln!("{}Lost connection to router", label);
+            }
+        }
+
+        if !reconnect {
+            return SessionEnd::Lost(Failure::Connection);
+        }
+
+        tokio::select! {
+            result = reconnect_with_backoff(max_attempts, label, &mut connect) => match result {
+                Ok(reconnected) => session = reconnected,
+                Err(failure) => return SessionEnd::Lost(failure),
+            },
+            _ = stop() => return SessionEnd::Stopped,
+        }
+    }
+}
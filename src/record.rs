@@ -0,0 +1,195 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as SerdeValue;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use xconn::async_::Value as EventValue;
+use xconn::sync::Value as WampValue;
+
+/// Key of the object that stands for a binary value, e.g. {"$bytes": "3q2+7w=="}.
+const BYTES_TAG: &str = "$bytes";
+
+/// How many publication ids the recorder remembers to skip duplicates.
+const RECENT_PUBLICATIONS: usize = 1024;
+
+/// One line of a `subscribe --record` file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since the Unix epoch at which the event was received
+    pub timestamp: u64,
+    pub topic: String,
+    pub publication: i64,
+    #[serde(default)]
+    pub args: Vec<SerdeValue>,
+    #[serde(default)]
+    pub kwargs: HashMap<String, SerdeValue>,
+}
+
+impl RecordedEvent {
+    pub fn new(
+        topic: &str,
+        publication: i64,
+        args: &[EventValue],
+        kwargs: &HashMap<String, EventValue>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            timestamp,
+            topic: topic.to_string(),
+            publication,
+            args: args.iter().map(encode_value).collect(),
+            kwargs: kwargs
+                .iter()
+                .map(|(k, v)| (k.clone(), encode_value(v)))
+                .collect(),
+        }
+    }
+}
+
+/// Converts an event value to JSON, tagging binary values so that replay restores them.
+fn encode_value(value: &EventValue) -> SerdeValue {
+    match value {
+        EventValue::Int(i) => SerdeValue::Number((*i).into()),
+        EventValue::Str(s) => SerdeValue::String(s.clone()),
+        EventValue::Bool(b) => SerdeValue::Bool(*b),
+        EventValue::Float(f) => serde_json::json!(f),
+        EventValue::List(l) => SerdeValue::Array(l.iter().map(encode_value).collect()),
+        EventValue::Dict(d) => SerdeValue::Object(
+            d.iter()
+                .map(|(k, v)| (k.clone(), encode_value(v)))
+                .collect(),
+        ),
+        EventValue::Bytes(b) => serde_json::json!({ BYTES_TAG: BASE64.encode(b) }),
+        _ => SerdeValue::Null,
+    }
+}
+
+/// Converts a recorded JSON value back to a WAMP value, decoding tagged binary values.
+pub fn decode_value(value: &SerdeValue) -> WampValue {
+    match value {
+        SerdeValue::Null => WampValue::Null,
+        SerdeValue::Bool(b) => WampValue::Bool(*b),
+        SerdeValue::Number(n) => match n.as_i64() {
+            Some(i) => WampValue::Int(i),
+            None => WampValue::Float(n.as_f64().unwrap_or_default()),
+        },
+        SerdeValue::String(s) => WampValue::Str(s.clone()),
+        SerdeValue::Array(l) => WampValue::List(l.iter().map(decode_value).collect()),
+        SerdeValue::Object(d) => {
+            if let (1, Some(SerdeValue::String(encoded))) = (d.len(), d.get(BYTES_TAG))
+                && let Ok(bytes) = BASE64.decode(encoded)
+            {
+                return WampValue::Bytes(bytes);
+            }
+            WampValue::Dict(
+                d.iter()
+                    .map(|(k, v)| (k.clone(), decode_value(v)))
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Writes received events to a file as newline-delimited JSON.
+/// Binary values are stored as {"$bytes": "<base64>"}.
+pub struct Recorder {
+    state: Mutex<RecorderState>,
+}
+
+struct RecorderState {
+    file: File,
+    recent: VecDeque<i64>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create record file '{}': {}", path, e))?;
+        Ok(Self {
+            state: Mutex::new(RecorderState {
+                file,
+                recent: VecDeque::with_capacity(RECENT_PUBLICATIONS),
+            }),
+        })
+    }
+
+    /// Appends an event, writing the whole line at once so the file can be followed with tail -f.
+    /// With --parallel every session receives the same publication, so it is only recorded once.
+    pub fn record(&self, event: &RecordedEvent) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.recent.contains(&event.publication) {
+            return Ok(());
+        }
+        if state.recent.len() == RECENT_PUBLICATIONS {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(event.publication);
+
+        let mut line = serde_json::to_string(event).map_err(|e| e.to_string())?;
+        line.push('\n');
+
+        state
+            .file
+            .write_all(line.as_bytes())
+            .map_err(|e| format!("Failed to record event: {}", e))
+    }
+}
+
+/// Reads the events of a record file in order.
+pub fn load(path: &str) -> Result<Vec<RecordedEvent>, String> {
+    let file =
+        File::open(path).map_err(|e| format!("Failed to open record file '{}': {}", path, e))?;
+
+    let mut events = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let event = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: invalid event: {}", path, index + 1, e))?;
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bytes_are_tagged() {
+        let value = EventValue::List(vec![EventValue::Bytes(vec![0xde, 0xad]), "dead".into()]);
+        assert_eq!(encode_value(&value), json!([{ "$bytes": "3q0=" }, "dead"]));
+    }
+
+    #[test]
+    fn tagged_bytes_are_decoded() {
+        let decoded = decode_value(&json!({ "data": { "$bytes": "3q0=" }, "name": "dead" }));
+        let WampValue::Dict(dict) = decoded else {
+            panic!("expected a dict, got {:?}", decoded);
+        };
+        assert!(matches!(&dict["data"], WampValue::Bytes(b) if b == &[0xde, 0xad]));
+        assert!(matches!(&dict["name"], WampValue::Str(s) if s == "dead"));
+    }
+
+    #[test]
+    fn objects_that_only_look_tagged_stay_dicts() {
+        let extra_key = decode_value(&json!({ "$bytes": "3q0=", "other": 1 }));
+        assert!(matches!(extra_key, WampValue::Dict(_)));
+
+        let not_base64 = decode_value(&json!({ "$bytes": "not base64!" }));
+        assert!(matches!(not_base64, WampValue::Dict(_)));
+    }
+}
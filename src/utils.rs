@@ -1,5 +1,9 @@
-use serde::Serialize;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use serde_json::Value as SerdeValue;
+use std::io::Read;
 use xconn::sync::Value as WampValue;
 
 #[macro_export]
@@ -54,70 +58,441 @@ pub enum ParsedArg {
     Float(f64),
     Boolean(bool),
     String(String),
+    Bytes(Vec<u8>),
+    List(Vec<SerdeValue>),
+    Dict(serde_json::Map<String, SerdeValue>),
+    Null,
 }
 
-pub fn parse_arg(input: &str) -> ParsedArg {
+impl From<ParsedArg> for WampValue {
+    fn from(arg: ParsedArg) -> Self {
+        match arg {
+            ParsedArg::Integer(v) => WampValue::Int(v),
+            ParsedArg::Float(v) => WampValue::Float(v),
+            ParsedArg::Boolean(v) => WampValue::Bool(v),
+            ParsedArg::String(v) => WampValue::Str(v),
+            ParsedArg::Bytes(v) => WampValue::Bytes(v),
+            ParsedArg::List(v) => serde_to_wamp_value(&SerdeValue::Array(v)),
+            ParsedArg::Dict(v) => serde_to_wamp_value(&SerdeValue::Object(v)),
+            ParsedArg::Null => WampValue::Null,
+        }
+    }
+}
+
+pub fn parse_arg(input: &str) -> Result<ParsedArg, String> {
     // Check for quoted strings to enforce string type
     if ((input.starts_with('\'') && input.ends_with('\''))
         || (input.starts_with('"') && input.ends_with('"')))
         && input.len() >= 2
     {
-        return ParsedArg::String(input[1..input.len() - 1].to_string());
+        return Ok(ParsedArg::String(input[1..input.len() - 1].to_string()));
+    }
+
+    // Binary values: bytes:hex:<hex>, bytes:base64:<base64> or bytes:@<file>
+    if let Some(bytes) = input.strip_prefix("bytes:") {
+        return parse_bytes(bytes)
+            .map(ParsedArg::Bytes)
+            .map_err(|e| format!("Invalid bytes value '{}': {}", input, e));
+    }
+
+    if input == "null" {
+        return Ok(ParsedArg::Null);
+    }
+
+    // JSON arrays and objects; anything that fails to parse stays a string
+    if input.starts_with('[') || input.starts_with('{') {
+        match serde_json::from_str(input) {
+            Ok(SerdeValue::Array(list)) => return Ok(ParsedArg::List(list)),
+            Ok(SerdeValue::Object(dict)) => return Ok(ParsedArg::Dict(dict)),
+            _ => {}
+        }
     }
 
     if let Ok(i) = input.parse::<i64>() {
-        return ParsedArg::Integer(i);
+        return Ok(ParsedArg::Integer(i));
     }
 
     if let Ok(f) = input.parse::<f64>() {
-        return ParsedArg::Float(f);
+        return Ok(ParsedArg::Float(f));
     }
 
     if let Ok(b) = input.parse::<bool>() {
-        return ParsedArg::Boolean(b);
+        return Ok(ParsedArg::Boolean(b));
     }
 
-    ParsedArg::String(input.to_string())
+    Ok(ParsedArg::String(input.to_string()))
 }
 
-#[derive(Serialize)]
+fn parse_bytes(input: &str) -> Result<Vec<u8>, String> {
+    if let Some(encoded) = input.strip_prefix("hex:") {
+        return hex::decode(encoded).map_err(|e| e.to_string());
+    }
+    if let Some(encoded) = input.strip_prefix("base64:") {
+        return BASE64.decode(encoded).map_err(|e| e.to_string());
+    }
+    if let Some(path) = input.strip_prefix('@') {
+        return std::fs::read(path).map_err(|e| format!("failed to read '{}': {}", path, e));
+    }
+    Err("expected hex:<hex>, base64:<base64> or @<file> after 'bytes:'".to_string())
+}
+
+/// Parses a command line value into a WAMP value.
+/// With `json` set, the value must be a JSON literal and no type guessing is done.
+pub fn parse_value(input: &str, json: bool) -> Result<WampValue, String> {
+    if json {
+        return serde_json::from_str(input)
+            .map(|v| serde_to_wamp_value(&v))
+            .map_err(|e| format!("Invalid JSON value '{}': {}", input, e));
+    }
+
+    parse_arg(input).map(WampValue::from)
+}
+
+/// Parses every command line value, so repeated calls and publishes reuse the result.
+pub fn parse_values(inputs: &[String], json: bool) -> Result<Vec<WampValue>, String> {
+    inputs
+        .iter()
+        .map(|input| parse_value(input, json))
+        .collect()
+}
+
+/// Parses a "key=value" string and returns the key and parsed value.
+pub fn parse_key_value(input: &str, json: bool) -> Result<(String, WampValue), String> {
+    match input.split_once('=') {
+        Some((key, value)) => Ok((key.to_string(), parse_value(value, json)?)),
+        None => Err(format!("Invalid '{}', expected KEY=VALUE", input)),
+    }
+}
+
+/// Parses every "key=value" string, see `parse_key_value`.
+pub fn parse_key_values(inputs: &[String], json: bool) -> Result<Vec<(String, WampValue)>, String> {
+    inputs
+        .iter()
+        .map(|input| parse_key_value(input, json))
+        .collect()
+}
+
+/// Parses a duration such as "500ms", "5s", "1.5m" or "2h". A bare number is taken as seconds.
+pub fn parse_duration(input: &str) -> Result<std::time::Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let value: f64 = number.parse().map_err(|_| {
+        format!(
+            "Invalid duration '{}', expected e.g. 500ms, 5s or 1m",
+            input
+        )
+    })?;
+    let seconds = match unit.trim() {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        other => {
+            return Err(format!(
+                "Invalid duration unit '{}', expected ms, s, m or h",
+                other
+            ));
+        }
+    };
+
+    std::time::Duration::try_from_secs_f64(seconds)
+        .map_err(|_| format!("Invalid duration '{}'", input))
+}
+
+/// Parses a --timeout. Like parse_duration, but a zero timeout is rejected, since it would
+/// give up before anything could be sent or received.
+pub fn parse_timeout(input: &str) -> Result<std::time::Duration, String> {
+    let timeout = parse_duration(input)?;
+    if timeout.is_zero() {
+        return Err(format!(
+            "Invalid timeout '{}', it must be above zero",
+            input
+        ));
+    }
+    Ok(timeout)
+}
+
+/// Parses a rate such as "500/s", "30/m" or "100/h" into messages per second.
+/// A bare number is taken as per second.
+pub fn parse_rate(input: &str) -> Result<f64, String> {
+    let (count, unit) = input.trim().split_once('/').unwrap_or((input.trim(), "s"));
+
+    let count: f64 = count
+        .trim()
+        .parse()
+        .ok()
+        .filter(|count: &f64| count.is_finite() && *count > 0.0)
+        .ok_or_else(|| format!("Invalid rate '{}', expected e.g. 500/s or 30/m", input))?;
+    let per_second = match unit.trim() {
+        "s" => count,
+        "m" => count / 60.0,
+        "h" => count / 3600.0,
+        other => {
+            return Err(format!("Invalid rate unit '{}', expected s, m or h", other));
+        }
+    };
+
+    Ok(per_second)
+}
+
+/// Reads a --topics-file: one topic per line, skipping blank lines and # comments.
+pub fn load_topics(path: &str) -> Result<Vec<String>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read topics file '{}': {}", path, e))?;
+
+    let topics: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+
+    if topics.is_empty() {
+        return Err(format!("Topics file '{}' contains no topics", path));
+    }
+    Ok(topics)
+}
+
+/// Arguments loaded with --payload: a JSON object with "args" and "kwargs",
+/// or a bare JSON array which is used as args.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Payload {
+    #[serde(default)]
+    pub args: Vec<SerdeValue>,
+    #[serde(default)]
+    pub kwargs: serde_json::Map<String, SerdeValue>,
+}
+
+impl Payload {
+    /// Reads a payload from stdin ("-") or from a file ("@path" or "path").
+    pub fn load(source: &str) -> Result<Self, String> {
+        let content = if source == "-" {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .map_err(|e| format!("Failed to read payload from stdin: {}", e))?;
+            content
+        } else {
+            let path = source.strip_prefix('@').unwrap_or(source);
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read payload '{}': {}", path, e))?
+        };
+
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let value: SerdeValue =
+            serde_json::from_str(content).map_err(|e| format!("Invalid payload JSON: {}", e))?;
+
+        match value {
+            SerdeValue::Array(args) => Ok(Self {
+                args,
+                kwargs: serde_json::Map::new(),
+            }),
+            SerdeValue::Object(_) => {
+                serde_json::from_value(value).map_err(|e| format!("Invalid payload: {}", e))
+            }
+            _ => Err(
+                "Payload must be a JSON object with \"args\" and \"kwargs\", or a JSON array"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+/// How binary values are printed in results, events and invocations.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum BytesFormat {
+    #[default]
+    Hex,
+    Base64,
+}
+
+impl BytesFormat {
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            BytesFormat::Hex => hex::encode(bytes),
+            BytesFormat::Base64 => BASE64.encode(bytes),
+        }
+    }
+}
+
+#[derive(Serialize, Default)]
 pub struct CommandOutput {
     pub args: Vec<SerdeValue>,
     pub kwargs: std::collections::HashMap<String, SerdeValue>,
+    /// The subscription an event arrived on, set when subscribed to several topics.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription: Option<String>,
+    /// Event metadata, set for subscriptions made with --details.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<std::collections::HashMap<String, SerdeValue>>,
+}
+
+/// A WAMP error returned by the router or a peer, with its payload.
+#[derive(Serialize)]
+pub struct ErrorOutput {
+    pub uri: String,
+    pub args: Vec<SerdeValue>,
+    pub kwargs: std::collections::HashMap<String, SerdeValue>,
+    #[serde(skip_serializing_if = "std::collections::HashMap::is_empty")]
+    pub details: std::collections::HashMap<String, SerdeValue>,
 }
 
-pub fn wamp_value_to_serde(v: &WampValue) -> SerdeValue {
+impl ErrorOutput {
+    pub fn new(error: &xconn::sync::Error, bytes_format: BytesFormat) -> Self {
+        let convert = |values: &std::collections::HashMap<String, WampValue>| {
+            values
+                .iter()
+                .map(|(k, v)| (k.clone(), wamp_value_to_serde(v, bytes_format)))
+                .collect()
+        };
+
+        Self {
+            uri: error.uri.clone(),
+            args: error
+                .args
+                .iter()
+                .flatten()
+                .map(|v| wamp_value_to_serde(v, bytes_format))
+                .collect(),
+            kwargs: error.kwargs.as_ref().map(convert).unwrap_or_default(),
+            details: error.details.as_ref().map(convert).unwrap_or_default(),
+        }
+    }
+}
+
+pub fn wamp_value_to_serde(v: &WampValue, bytes_format: BytesFormat) -> SerdeValue {
     match v {
         WampValue::Int(i) => SerdeValue::Number((*i).into()),
         WampValue::Str(s) => SerdeValue::String(s.clone()),
         WampValue::Bool(b) => SerdeValue::Bool(*b),
         WampValue::Float(f) => serde_json::json!(f),
-        WampValue::List(l) => SerdeValue::Array(l.iter().map(wamp_value_to_serde).collect()),
+        WampValue::List(l) => SerdeValue::Array(
+            l.iter()
+                .map(|v| wamp_value_to_serde(v, bytes_format))
+                .collect(),
+        ),
         WampValue::Dict(d) => SerdeValue::Object(
             d.iter()
-                .map(|(k, v)| (k.clone(), wamp_value_to_serde(v)))
+                .map(|(k, v)| (k.clone(), wamp_value_to_serde(v, bytes_format)))
                 .collect(),
         ),
-        WampValue::Bytes(_) => SerdeValue::String("<binary>".to_string()),
+        WampValue::Bytes(b) => SerdeValue::String(bytes_format.encode(b)),
         _ => SerdeValue::Null,
     }
 }
 
-pub fn wamp_async_value_to_serde(v: &xconn::async_::Value) -> SerdeValue {
+pub fn wamp_async_value_to_serde(
+    v: &xconn::async_::Value,
+    bytes_format: BytesFormat,
+) -> SerdeValue {
     match v {
         xconn::async_::Value::Int(i) => SerdeValue::Number((*i).into()),
         xconn::async_::Value::Str(s) => SerdeValue::String(s.clone()),
         xconn::async_::Value::Bool(b) => SerdeValue::Bool(*b),
         xconn::async_::Value::Float(f) => serde_json::json!(f),
-        xconn::async_::Value::List(l) => {
-            SerdeValue::Array(l.iter().map(wamp_async_value_to_serde).collect())
-        }
+        xconn::async_::Value::List(l) => SerdeValue::Array(
+            l.iter()
+                .map(|v| wamp_async_value_to_serde(v, bytes_format))
+                .collect(),
+        ),
         xconn::async_::Value::Dict(d) => SerdeValue::Object(
             d.iter()
-                .map(|(k, v)| (k.clone(), wamp_async_value_to_serde(v)))
+                .map(|(k, v)| (k.clone(), wamp_async_value_to_serde(v, bytes_format)))
                 .collect(),
         ),
-        xconn::async_::Value::Bytes(_) => SerdeValue::String("<binary>".to_string()),
+        xconn::async_::Value::Bytes(b) => SerdeValue::String(bytes_format.encode(b)),
         _ => SerdeValue::Null,
     }
 }
+
+pub fn serde_to_wamp_value(v: &SerdeValue) -> WampValue {
+    match v {
+        SerdeValue::Null => WampValue::Null,
+        SerdeValue::Bool(b) => WampValue::Bool(*b),
+        SerdeValue::Number(n) => match n.as_i64() {
+            Some(i) => WampValue::Int(i),
+            None => WampValue::Float(n.as_f64().unwrap_or_default()),
+        },
+        SerdeValue::String(s) => WampValue::Str(s.clone()),
+        SerdeValue::Array(l) => WampValue::List(l.iter().map(serde_to_wamp_value).collect()),
+        SerdeValue::Object(d) => WampValue::Dict(
+            d.iter()
+                .map(|(k, v)| (k.clone(), serde_to_wamp_value(v)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_key_value_splits_at_the_first_equals() {
+        assert_eq!(
+            parse_key_value("query=a=b", false),
+            Ok(("query".to_string(), WampValue::Str("a=b".to_string())))
+        );
+        assert_eq!(
+            parse_key_value("limit=10", false),
+            Ok(("limit".to_string(), WampValue::Int(10)))
+        );
+    }
+
+    #[test]
+    fn parse_key_value_requires_equals() {
+        assert!(parse_key_value("limit", false).is_err());
+        assert!(parse_key_values(&["a=1".to_string(), "b".to_string()], false).is_err());
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("0"), Ok(Duration::ZERO));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("5"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid_input() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+
+    #[test]
+    fn parse_timeout_rejects_zero() {
+        assert_eq!(parse_timeout("250ms"), Ok(Duration::from_millis(250)));
+        assert!(parse_timeout("0").is_err());
+        assert!(parse_timeout("0ms").is_err());
+    }
+
+    #[test]
+    fn parse_rate_units() {
+        assert_eq!(parse_rate("10/s"), Ok(10.0));
+        assert_eq!(parse_rate("10"), Ok(10.0));
+        assert_eq!(parse_rate("30/m"), Ok(0.5));
+        assert_eq!(parse_rate("7200/h"), Ok(2.0));
+    }
+
+    #[test]
+    fn parse_rate_rejects_invalid_input() {
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("0/s").is_err());
+        assert!(parse_rate("-5/s").is_err());
+        assert!(parse_rate("10/d").is_err());
+        assert!(parse_rate("fast").is_err());
+    }
+}